@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode};
+
+use openmls_test::attachment::{send_mls_attachment, send_ratchet_attachment};
+use openmls_test::key_service::KeyService;
+use openmls_test::mls::{create_bare_group_with_members, BenchConfig};
+use openmls_test::ratchet::RatchetGroup;
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(1)).sample_size(10);
+    targets = send_attachment
+}
+criterion_main!(benches);
+
+// Largest total size of the pairwise copies of one attachment, which only
+// leaves out 100MB sent to 100 members
+const MAX_PAIRWISE_MB: usize = 1024;
+
+fn send_attachment(c: &mut Criterion) {
+    let config = BenchConfig::default();
+    let mut bench_group = c.benchmark_group("attachment");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+
+    for attachment_size in [1, 10, 100] {
+        let attachment = vec![1u8; attachment_size * 1024 * 1024];
+        for count in [2, 100] {
+            let mut ratchet_group = RatchetGroup::with_generated_members(count);
+            let mut key_service = KeyService::new();
+            key_service
+                .generate(&config.ciphersuite, &config.provider, count)
+                .expect("Failed to populate KeyService");
+            let mut mls_group = create_bare_group_with_members(&config, &key_service);
+
+            let scenario_name = format!("{}_{}mb", count, attachment_size);
+
+            bench_group.bench_function(
+                BenchmarkId::new("TreeKEM message", &scenario_name),
+                |bencher| {
+                    bencher.iter(|| {
+                        mls_group
+                            .create_message(&config.provider, &config.self_signer, &attachment)
+                            .expect("Failed to create MLS message");
+                    });
+                },
+            );
+            bench_group.bench_function(
+                BenchmarkId::new("TreeKEM attachment", &scenario_name),
+                |bencher| {
+                    bencher.iter(|| {
+                        send_mls_attachment(&mut mls_group, &config, &attachment)
+                            .expect("Failed to send attachment");
+                    });
+                },
+            );
+            // Pairwise messages hold a copy of the attachment per member,
+            // so they are skipped once that stops fitting in memory
+            if count * attachment_size <= MAX_PAIRWISE_MB {
+                bench_group.bench_function(
+                    BenchmarkId::new("Pairwise Ratchet message", &scenario_name),
+                    |bencher| {
+                        bencher.iter(|| {
+                            ratchet_group.encrypt_message(&attachment);
+                        });
+                    },
+                );
+            }
+            bench_group.bench_function(
+                BenchmarkId::new("Pairwise Ratchet attachment", &scenario_name),
+                |bencher| {
+                    bencher.iter(|| {
+                        send_ratchet_attachment(&mut ratchet_group, &attachment)
+                            .expect("Failed to send attachment");
+                    });
+                },
+            );
+        }
+    }
+    bench_group.finish();
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, SamplingMode};
+use openmls::prelude::*;
+use openmls::treesync::RatchetTreeIn;
+
+use openmls_test::credential::{create_keypackage, make_credential};
+use openmls_test::key_service::KeyService;
+use openmls_test::mls::{
+    create_bare_group_with_joined_members, join_by_external_commit, join_from_welcome,
+    receive_message, BenchConfig, MemberState,
+};
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(1)).sample_size(10);
+    targets = welcome_join, external_join
+}
+criterion_main!(benches);
+
+// Group of `count` members plus one existing member whose processing cost is
+// measured.
+fn group_with_observer(config: &BenchConfig, count: usize) -> (MlsGroup, MemberState) {
+    let mut key_service = KeyService::new();
+    key_service
+        .generate(&config.ciphersuite, &config.provider, count)
+        .expect("Failed to populate KeyService");
+
+    let (mls_group, mut member_states) =
+        create_bare_group_with_joined_members(config, &key_service, 1);
+    (mls_group, member_states.remove(0))
+}
+
+fn welcome_join(c: &mut Criterion) {
+    let config = BenchConfig::default();
+
+    let mut bench_group = c.benchmark_group("join_welcome");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+    for count in [2, 100, 1024] {
+        let setup = || {
+            let (mut mls_group, observer) = group_with_observer(&config, count);
+            let (credential, signer) =
+                make_credential(&config.ciphersuite, &config.provider, "Bob".into())
+                    .expect("Failed to create credential");
+            let key_package =
+                create_keypackage(config.ciphersuite, &config.provider, credential, &signer)
+                    .expect("Failed to create KeyPackage");
+
+            let (commit, welcome, _) = mls_group
+                .add_members(&config.provider, &config.self_signer, &[key_package])
+                .expect("Failed to add members");
+            mls_group
+                .merge_pending_commit(&config.provider)
+                .expect("Failed to merge pending commits");
+            let ratchet_tree_in: RatchetTreeIn = mls_group.export_ratchet_tree().into();
+            (observer, commit, welcome, ratchet_tree_in)
+        };
+
+        bench_group.bench_function(BenchmarkId::new("Joiner", count), |bencher| {
+            bencher.iter_batched(
+                setup,
+                |(_, _, welcome, ratchet_tree_in)| {
+                    join_from_welcome(&config, &welcome, ratchet_tree_in)
+                        .expect("Failed to join from Welcome");
+                },
+                BatchSize::LargeInput,
+            );
+        });
+        bench_group.bench_function(BenchmarkId::new("Member", count), |bencher| {
+            bencher.iter_batched(
+                setup,
+                |(mut observer, commit, _, _)| {
+                    receive_message(&mut observer.group, &config.provider, &commit)
+                        .expect("Failed to process commit");
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    bench_group.finish();
+}
+
+fn external_join(c: &mut Criterion) {
+    let config = BenchConfig::default();
+
+    let mut bench_group = c.benchmark_group("join_external");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+    for count in [2, 100, 1024] {
+        let setup = || {
+            let (mls_group, observer) = group_with_observer(&config, count);
+            let group_info = mls_group
+                .export_group_info(&config.provider, &config.self_signer, false)
+                .expect("Failed to export GroupInfo");
+            let ratchet_tree_in: RatchetTreeIn = mls_group.export_ratchet_tree().into();
+            let (credential, signer) =
+                make_credential(&config.ciphersuite, &config.provider, "Bob".into())
+                    .expect("Failed to create credential");
+            (observer, group_info, ratchet_tree_in, credential, signer)
+        };
+
+        bench_group.bench_function(BenchmarkId::new("Joiner", count), |bencher| {
+            bencher.iter_batched(
+                setup,
+                |(_, group_info, ratchet_tree_in, credential, signer)| {
+                    join_by_external_commit(
+                        &config,
+                        &group_info,
+                        ratchet_tree_in,
+                        credential,
+                        &signer,
+                    )
+                    .expect("Failed to join by external commit");
+                },
+                BatchSize::LargeInput,
+            );
+        });
+        bench_group.bench_function(BenchmarkId::new("Member", count), |bencher| {
+            bencher.iter_batched(
+                || {
+                    let (observer, group_info, ratchet_tree_in, credential, signer) = setup();
+                    let (_, commit) = join_by_external_commit(
+                        &config,
+                        &group_info,
+                        ratchet_tree_in,
+                        credential,
+                        &signer,
+                    )
+                    .expect("Failed to join by external commit");
+                    (observer, commit)
+                },
+                |(mut observer, commit)| {
+                    receive_message(&mut observer.group, &config.provider, &commit)
+                        .expect("Failed to process external commit");
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    bench_group.finish();
+}
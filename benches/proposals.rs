@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, SamplingMode};
+
+use openmls_test::credential::{create_keypackage, make_credential};
+use openmls_test::key_service::KeyService;
+use openmls_test::mls::{
+    create_bare_group_with_joined_members, receive_message, BenchConfig, MemberState,
+};
+use openmls_test::proposals::{commit_pending, propose_add, propose_remove, propose_update};
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(1)).sample_size(10);
+    targets = commit_proposals
+}
+criterion_main!(benches);
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Add,
+    Remove,
+    Update,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Add => "add",
+            Kind::Remove => "remove",
+            Kind::Update => "update",
+        }
+    }
+}
+
+// Adds and removes rotate through this many proposers, a member can only
+// propose one update per epoch
+const PROPOSERS: usize = 10;
+
+// The first member commits, the second only observes, and the others
+// propose `pending` proposals of `kind`.
+fn members_with_pending(
+    config: &BenchConfig,
+    count: usize,
+    pending: usize,
+    kind: Kind,
+) -> (MemberState, MemberState) {
+    let mut key_service = KeyService::new();
+    key_service
+        .generate(&config.ciphersuite, &config.provider, count)
+        .expect("Failed to populate KeyService");
+
+    let proposers = match kind {
+        Kind::Update => pending,
+        Kind::Add | Kind::Remove => pending.min(PROPOSERS),
+    };
+    let (_, mut member_states) =
+        create_bare_group_with_joined_members(config, &key_service, proposers + 2);
+    let mut committer = member_states.remove(0);
+    let mut observer = member_states.remove(0);
+
+    // Removes target members that were not joined
+    let joined: Vec<_> = [&committer, &observer]
+        .into_iter()
+        .chain(&member_states)
+        .map(|state| state.group.own_leaf_index())
+        .collect();
+    let targets: Vec<_> = committer
+        .group
+        .members()
+        .map(|member| member.index)
+        .filter(|index| !joined.contains(index))
+        .collect();
+
+    for i in 0..pending {
+        let proposer = &mut member_states[i % proposers];
+        let proposal = match kind {
+            Kind::Add => {
+                let (credential, signer) = make_credential(
+                    &config.ciphersuite,
+                    &config.provider,
+                    format!("Joiner {}", i),
+                )
+                .expect("Failed to create credential");
+                let key_package =
+                    create_keypackage(config.ciphersuite, &config.provider, credential, &signer)
+                        .expect("Failed to create KeyPackage");
+                propose_add(
+                    &mut proposer.group,
+                    &config.provider,
+                    &proposer.signer,
+                    &key_package,
+                )
+            }
+            Kind::Remove => propose_remove(
+                &mut proposer.group,
+                &config.provider,
+                &proposer.signer,
+                targets[i],
+            ),
+            Kind::Update => propose_update(&mut proposer.group, &config.provider, &proposer.signer),
+        }
+        .expect("Failed to propose");
+        receive_message(&mut committer.group, &config.provider, &proposal)
+            .expect("Failed to store proposal");
+        receive_message(&mut observer.group, &config.provider, &proposal)
+            .expect("Failed to store proposal");
+    }
+
+    (committer, observer)
+}
+
+fn commit_proposals(c: &mut Criterion) {
+    let config = BenchConfig::default();
+
+    let mut bench_group = c.benchmark_group("commit_proposals");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+    for count in [100, 1024] {
+        for (kind, pending) in [Kind::Add, Kind::Remove, Kind::Update]
+            .into_iter()
+            .flat_map(|kind| [1, 10, 50].map(|pending| (kind, pending)))
+        {
+            let scenario_name = format!("{}_{}_{}", count, pending, kind.name());
+
+            bench_group.bench_function(BenchmarkId::new("Commit", &scenario_name), |bencher| {
+                bencher.iter_batched(
+                    || members_with_pending(&config, count, pending, kind).0,
+                    |mut committer| {
+                        commit_pending(&mut committer.group, &config.provider, &committer.signer)
+                            .expect("Failed to commit proposals");
+                    },
+                    BatchSize::LargeInput,
+                );
+            });
+            bench_group.bench_function(BenchmarkId::new("Process", &scenario_name), |bencher| {
+                bencher.iter_batched(
+                    || {
+                        let (mut committer, observer) =
+                            members_with_pending(&config, count, pending, kind);
+                        let (commit, _) = commit_pending(
+                            &mut committer.group,
+                            &config.provider,
+                            &committer.signer,
+                        )
+                        .expect("Failed to commit proposals");
+                        (observer, commit)
+                    },
+                    |(mut observer, commit)| {
+                        receive_message(&mut observer.group, &config.provider, &commit)
+                            .expect("Failed to process commit");
+                    },
+                    BatchSize::LargeInput,
+                );
+            });
+        }
+    }
+    bench_group.finish();
+}
@@ -6,13 +6,17 @@ use openmls::prelude::*;
 
 use openmls_test::credential::{create_keypackage, make_credential};
 use openmls_test::key_service::KeyService;
-use openmls_test::mls::{create_group, create_group_with_members, BenchConfig};
+use openmls_test::mls::{
+    create_group, create_group_with_last_member, create_group_with_members, receive_message,
+    BenchConfig,
+};
+use openmls_test::proposals::{commit_pending, propose_leave};
 use openmls_test::ratchet::RatchetGroup;
 
 criterion_group! {
     name = benches;
     config = Criterion::default().measurement_time(Duration::from_secs(1)).sample_size(10);
-    targets = remove_member
+    targets = remove_member, leave_group
 }
 criterion_main!(benches);
 
@@ -80,3 +84,57 @@ fn remove_member(c: &mut Criterion) {
     }
     bench_group.finish();
 }
+
+fn leave_group(c: &mut Criterion) {
+    let config = BenchConfig::default();
+
+    let mut bench_group = c.benchmark_group("leave");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+    for count in [2, 100, 1024] {
+        bench_group.bench_with_input(
+            BenchmarkId::new("TreeKEM", count),
+            &count,
+            |bencher, &count| {
+                bencher.iter_batched(
+                    || {
+                        let mut key_service = KeyService::new();
+                        key_service
+                            .generate(&config.ciphersuite, &config.provider, count)
+                            .expect("Failed to populate KeyService");
+
+                        // The leaver needs its own view of the group to send
+                        // the self-remove proposal. The tree is mended like in
+                        // the remove benchmark, so only the operation differs
+                        create_group_with_last_member(&config, &key_service)
+                    },
+                    |(mut group, mut leaver)| {
+                        let proposal =
+                            propose_leave(&mut leaver.group, &config.provider, &leaver.signer)
+                                .expect("Failed to propose leaving");
+                        receive_message(&mut group, &config.provider, &proposal)
+                            .expect("Failed to store proposal");
+                        commit_pending(&mut group, &config.provider, &config.self_signer)
+                            .expect("Failed to commit the leave");
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+        // The rest of the group rekeys over the pairwise sessions, which the
+        // optimized scheme shares, so both schemes leave the same way
+        bench_group.bench_function(BenchmarkId::new("Pairwise Ratchet", count), |bencher| {
+            bencher.iter_batched(
+                || RatchetGroup::with_generated_members(count),
+                |mut ratchet_group| {
+                    ratchet_group.leave(0, b"leave");
+                    ratchet_group.rekey(b"leave").expect("Failed to rekey");
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    bench_group.finish();
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, SamplingMode};
+
+use openmls_test::ratchet::{RatchetGroup, RatchetMessage, MAX_SKIPPED_KEYS};
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(1)).sample_size(10);
+    targets = decrypt_after_gap
+}
+criterion_main!(benches);
+
+// A single member sends `gap + 1` messages, only the last one has arrived
+fn gap_setup(gap: usize) -> (RatchetGroup, Vec<RatchetMessage>) {
+    let mut ratchet_group = RatchetGroup::with_generated_members(1);
+    let messages = (0..=gap)
+        .map(|_| ratchet_group.encrypt_from_member(0, b"reordered message"))
+        .collect();
+    (ratchet_group, messages)
+}
+
+fn decrypt_after_gap(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("reorder");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+    // Up to the largest gap the ratchet accepts
+    for gap in [0, 10, 50, MAX_SKIPPED_KEYS] {
+        bench_group.bench_with_input(BenchmarkId::new("Skip", gap), &gap, |bencher, &gap| {
+            bencher.iter_batched(
+                || gap_setup(gap),
+                |(mut ratchet_group, messages)| {
+                    let (header, ciphertext, nonce) = &messages[gap];
+                    ratchet_group
+                        .try_decrypt_message(0, header, ciphertext, nonce)
+                        .expect("Failed to decrypt message");
+                },
+                BatchSize::LargeInput,
+            );
+        });
+        bench_group.bench_with_input(BenchmarkId::new("Cached", gap), &gap, |bencher, &gap| {
+            bencher.iter_batched(
+                || {
+                    let (mut ratchet_group, messages) = gap_setup(gap);
+                    let (header, ciphertext, nonce) = &messages[gap];
+                    ratchet_group
+                        .try_decrypt_message(0, header, ciphertext, nonce)
+                        .expect("Failed to decrypt message");
+                    (ratchet_group, messages)
+                },
+                |(mut ratchet_group, messages)| {
+                    // The oldest message, decrypted from a full cache
+                    let (header, ciphertext, nonce) = &messages[0];
+                    if gap > 0 {
+                        ratchet_group
+                            .try_decrypt_message(0, header, ciphertext, nonce)
+                            .expect("Failed to decrypt message");
+                    }
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    bench_group.finish();
+}
@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, SamplingMode};
+use openmls::prelude::CredentialWithKey;
+
+use openmls_test::key_service::KeyService;
+use openmls_test::mls::{
+    create_bare_group_with_member_states, own_signature_key, receive_message, BenchConfig,
+};
+use openmls_test::ratchet::RatchetGroup;
+use openmls_test::revocation::{
+    receive_enforcing_revocation, receive_ratchet_enforcing_revocation,
+};
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(1)).sample_size(10);
+    targets = revoke_member
+}
+criterion_main!(benches);
+
+// Time from the revoked member's next message until every remaining member
+// has processed the removal.
+fn revoke_member(c: &mut Criterion) {
+    let config = BenchConfig::default();
+
+    let mut bench_group = c.benchmark_group("revoke");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+    for count in [2, 100, 1024] {
+        bench_group.bench_with_input(
+            BenchmarkId::new("TreeKEM", count),
+            &count,
+            |bencher, &count| {
+                bencher.iter_batched(
+                    || {
+                        let mut key_service = KeyService::new();
+                        key_service
+                            .generate(&config.ciphersuite, &config.provider, count)
+                            .expect("Failed to populate KeyService");
+                        key_service.trust(&config.self_credential);
+
+                        let (mls_group, mut member_states) =
+                            create_bare_group_with_member_states(&config, &key_service);
+
+                        let revoked = member_states.remove(0);
+                        let revoked_key = own_signature_key(&revoked.group);
+                        let identity = key_service
+                            .member_by_signature_key(&revoked_key)
+                            .expect("Unknown member")
+                            .credential
+                            .credential
+                            .identity()
+                            .to_vec();
+                        key_service
+                            .revoke(&identity)
+                            .expect("Failed to revoke member");
+
+                        (key_service, mls_group, revoked, member_states)
+                    },
+                    |(key_service, mut group, mut revoked, mut member_states)| {
+                        let message = revoked
+                            .group
+                            .create_message(&config.provider, &revoked.signer, b"hello")
+                            .expect("Failed to create MLS message");
+
+                        let (_, removal) = receive_enforcing_revocation(
+                            &mut group,
+                            &config.provider,
+                            &config.self_signer,
+                            &message,
+                            &key_service,
+                        )
+                        .expect("Failed to process message");
+                        let removal = removal.expect("Revocation was not detected");
+
+                        for member in member_states.iter_mut() {
+                            receive_enforcing_revocation(
+                                &mut member.group,
+                                &config.provider,
+                                &member.signer,
+                                &message,
+                                &key_service,
+                            )
+                            .expect("Failed to process message");
+                            receive_message(&mut member.group, &config.provider, &removal)
+                                .expect("Failed to process removal");
+                        }
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+        // Members drop the revoked session and rekey independently, so one
+        // member's work is measured. The optimized scheme uses the same
+        // sessions and revokes the same way.
+        bench_group.bench_function(BenchmarkId::new("Pairwise Ratchet", count), |bencher| {
+            bencher.iter_batched(
+                || {
+                    let mut key_service = KeyService::new();
+                    key_service
+                        .generate(&config.ciphersuite, &config.provider, count)
+                        .expect("Failed to populate KeyService");
+                    let peers: Vec<CredentialWithKey> = key_service
+                        .all_data()
+                        .into_iter()
+                        .map(|member| member.credential.clone())
+                        .collect();
+
+                    let identity = key_service
+                        .member_by_signature_key(peers[0].signature_key.as_slice())
+                        .expect("Unknown member")
+                        .credential
+                        .credential
+                        .identity()
+                        .to_vec();
+                    key_service
+                        .revoke(&identity)
+                        .expect("Failed to revoke member");
+
+                    let ratchet_group = RatchetGroup::with_generated_members(count);
+                    (key_service, ratchet_group, peers)
+                },
+                |(key_service, mut ratchet_group, mut peers)| {
+                    let message = ratchet_group.encrypt_from_member(0, b"hello");
+                    let (_, rekey) = receive_ratchet_enforcing_revocation(
+                        &mut ratchet_group,
+                        &mut peers,
+                        0,
+                        &message,
+                        &key_service,
+                    )
+                    .expect("Failed to process message");
+                    rekey.expect("Revocation was not detected");
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    bench_group.finish();
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, SamplingMode};
+use openmls::prelude::*;
+
+use openmls_test::key_service::KeyService;
+use openmls_test::mls::{create_bare_group_with_joined_members, receive_message, BenchConfig};
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(1)).sample_size(10);
+    targets = handshake_wire_format
+}
+criterion_main!(benches);
+
+fn handshake_wire_format(c: &mut Criterion) {
+    let policies = [
+        ("PublicMessage", PURE_PLAINTEXT_WIRE_FORMAT_POLICY),
+        ("PrivateMessage", PURE_CIPHERTEXT_WIRE_FORMAT_POLICY),
+    ];
+
+    let mut bench_group = c.benchmark_group("wire_format");
+    bench_group
+        .measurement_time(Duration::from_secs(1))
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat);
+    for count in [2, 100, 1024] {
+        for (name, policy) in policies {
+            let config = BenchConfig::with_wire_format_policy(policy);
+            let setup = || {
+                let mut key_service = KeyService::new();
+                key_service
+                    .generate(&config.ciphersuite, &config.provider, count)
+                    .expect("Failed to populate KeyService");
+                let (mls_group, mut member_states) =
+                    create_bare_group_with_joined_members(&config, &key_service, 1);
+                (mls_group, member_states.remove(0))
+            };
+
+            bench_group.bench_function(
+                BenchmarkId::new(format!("{} commit", name), count),
+                |bencher| {
+                    bencher.iter_batched(
+                        || setup().0,
+                        |mut group| {
+                            group
+                                .self_update(&config.provider, &config.self_signer)
+                                .expect("Failed to update own leaf node");
+                            group
+                                .merge_pending_commit(&config.provider)
+                                .expect("Failed to merge pending commits");
+                        },
+                        BatchSize::LargeInput,
+                    );
+                },
+            );
+            bench_group.bench_function(
+                BenchmarkId::new(format!("{} process", name), count),
+                |bencher| {
+                    bencher.iter_batched(
+                        || {
+                            let (mut group, observer) = setup();
+                            let (commit, _, _) = group
+                                .self_update(&config.provider, &config.self_signer)
+                                .expect("Failed to update own leaf node");
+                            (observer, commit)
+                        },
+                        |(mut observer, commit)| {
+                            receive_message(&mut observer.group, &config.provider, &commit)
+                                .expect("Failed to process commit");
+                        },
+                        BatchSize::LargeInput,
+                    );
+                },
+            );
+        }
+    }
+    bench_group.finish();
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use double_ratchet_2::ratchet::Ratchet;
+use openmls::prelude::*;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::interval;
+use x25519_dalek::StaticSecret;
+
+use crate::delivery::{ClientId, DeliveryService, DeliveryStats, Envelope};
+use crate::key_service::KeyService;
+use crate::mls::{create_bare_group_with_member_states, receive_message, BenchConfig, MemberState};
+use crate::ratchet::{RatchetGroup, RatchetMessage};
+
+#[derive(Clone, Debug)]
+pub struct ActorConfig {
+    /// Group size, including the creator
+    pub members: usize,
+    /// Ticks of every member, each sending a message or a commit
+    pub ticks: usize,
+    pub tick_interval: Duration,
+    /// Every `commit_every`-th tick commits an update instead, 0 never does
+    pub commit_every: usize,
+    pub message_size: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ActorReport {
+    pub wall_time: Duration,
+    pub delivered: usize,
+    pub undecryptable: usize,
+    pub commits_accepted: usize,
+    pub commits_rejected: usize,
+    /// Time from upload until the recipient started processing, summed over
+    /// all deliveries
+    pub total_queueing_delay: Duration,
+    pub max_queueing_delay: Duration,
+    pub delivery: DeliveryStats,
+}
+
+impl ActorReport {
+    pub fn mean_queueing_delay(&self) -> Duration {
+        self.total_queueing_delay / self.delivered.max(1) as u32
+    }
+
+    /// Delivered messages per second.
+    pub fn throughput(&self) -> f64 { self.delivered as f64 / self.wall_time.as_secs_f64() }
+}
+
+enum Upload {
+    Message(MlsMessageOut),
+    Commit {
+        epoch: u64,
+        commit: MlsMessageOut,
+        accepted: oneshot::Sender<bool>,
+    },
+    Ratchet(Vec<(ClientId, RatchetMessage)>),
+}
+
+struct Request {
+    sender: ClientId,
+    sent_at: Instant,
+    upload: Upload,
+}
+
+struct Delivery {
+    sent_at: Instant,
+    envelope: Envelope,
+}
+
+#[derive(Default)]
+struct MemberStats {
+    delivered: usize,
+    undecryptable: usize,
+    commits_accepted: usize,
+    commits_rejected: usize,
+    total_queueing_delay: Duration,
+    max_queueing_delay: Duration,
+}
+
+impl MemberStats {
+    fn record_delay(&mut self, sent_at: Instant) {
+        let delay = sent_at.elapsed();
+        self.total_queueing_delay += delay;
+        self.max_queueing_delay = self.max_queueing_delay.max(delay);
+    }
+}
+
+// Owns the delivery service and pushes everything it accepts into the
+// inboxes right away. Returns once every member dropped its request channel.
+async fn run_server(
+    group_id: GroupId,
+    mut delivery: DeliveryService,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+    inboxes: Vec<mpsc::UnboundedSender<Delivery>>,
+) -> DeliveryStats {
+    while let Some(Request {
+        sender,
+        sent_at,
+        upload,
+    }) = requests.recv().await
+    {
+        let mut reply = None;
+        match upload {
+            Upload::Message(message) => {
+                // Only fails for unknown groups
+                let _ = delivery.send(&group_id, sender, &message);
+            }
+            Upload::Commit {
+                epoch,
+                commit,
+                accepted,
+            } => {
+                let result = delivery.submit_commit(&group_id, sender, epoch, &commit);
+                reply = Some((accepted, result.is_ok()));
+            }
+            Upload::Ratchet(messages) => delivery.send_ratchet(sender, messages),
+        }
+        for (client, inbox) in inboxes.iter().enumerate() {
+            for envelope in delivery.fetch(client) {
+                // Members that already finished don't read any more
+                let _ = inbox.send(Delivery { sent_at, envelope });
+            }
+        }
+        if let Some((accepted, result)) = reply {
+            let _ = accepted.send(result);
+        }
+    }
+    delivery.stats().clone()
+}
+
+async fn run_mls_member(
+    bench_config: Arc<BenchConfig>,
+    actor_config: ActorConfig,
+    client: ClientId,
+    mut state: MemberState,
+    requests: mpsc::UnboundedSender<Request>,
+    mut inbox: mpsc::UnboundedReceiver<Delivery>,
+) -> Result<MemberStats> {
+    let mut stats = MemberStats::default();
+    let mut requests = Some(requests);
+    let mut ticker = interval(actor_config.tick_interval);
+    let mut ticks = 0;
+    let message = vec![0u8; actor_config.message_size];
+
+    loop {
+        tokio::select! {
+            delivery = inbox.recv() => {
+                let Some(Delivery { sent_at, envelope }) = delivery else {
+                    break;
+                };
+                stats.record_delay(sent_at);
+                let Envelope::Mls { message, .. } = envelope else {
+                    bail!("Not an MLS message");
+                };
+                match receive_message(&mut state.group, &bench_config.provider, &message) {
+                    Ok(_) => stats.delivered += 1,
+                    // Sent in an epoch this member already left
+                    Err(_) => stats.undecryptable += 1,
+                }
+            }
+            _ = ticker.tick(), if requests.is_some() => {
+                let Some(sender) = requests.as_ref() else {
+                    continue;
+                };
+                if ticks == actor_config.ticks {
+                    // Lets the server stop once all members are done
+                    requests = None;
+                    continue;
+                }
+                ticks += 1;
+
+                let group = &mut state.group;
+                if actor_config.commit_every > 0 && ticks % actor_config.commit_every == 0 {
+                    let epoch = group.epoch().as_u64();
+                    let (commit, _, _) = group.self_update(&bench_config.provider, &state.signer)?;
+                    let (accepted, reply) = oneshot::channel();
+                    sender.send(Request {
+                        sender: client,
+                        sent_at: Instant::now(),
+                        upload: Upload::Commit { epoch, commit, accepted },
+                    })
+                    .map_err(|_| anyhow!("Delivery service stopped"))?;
+                    if reply.await? {
+                        group.merge_pending_commit(&bench_config.provider)?;
+                        stats.commits_accepted += 1;
+                    } else {
+                        group.clear_pending_commit();
+                        stats.commits_rejected += 1;
+                    }
+                } else {
+                    let application_message =
+                        group.create_message(&bench_config.provider, &state.signer, &message)?;
+                    sender.send(Request {
+                        sender: client,
+                        sent_at: Instant::now(),
+                        upload: Upload::Message(application_message),
+                    })
+                    .map_err(|_| anyhow!("Delivery service stopped"))?;
+                }
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+async fn run_ratchet_member(
+    actor_config: ActorConfig,
+    client: ClientId,
+    mut sessions: HashMap<ClientId, Ratchet<StaticSecret>>,
+    requests: mpsc::UnboundedSender<Request>,
+    mut inbox: mpsc::UnboundedReceiver<Delivery>,
+) -> Result<MemberStats> {
+    let mut stats = MemberStats::default();
+    let mut requests = Some(requests);
+    let mut ticker = interval(actor_config.tick_interval);
+    let mut ticks = 0;
+    let message = vec![0u8; actor_config.message_size];
+
+    loop {
+        tokio::select! {
+            delivery = inbox.recv() => {
+                let Some(Delivery { sent_at, envelope }) = delivery else {
+                    break;
+                };
+                stats.record_delay(sent_at);
+                let Envelope::Ratchet { sender, message } = envelope else {
+                    bail!("Not a ratchet message");
+                };
+                let Some(session) = sessions.get_mut(&sender) else {
+                    bail!("No session with {}", sender);
+                };
+                let (header, ciphertext, nonce) = message;
+                session.ratchet_decrypt(&header, &ciphertext, &nonce, &[]);
+                stats.delivered += 1;
+            }
+            _ = ticker.tick(), if requests.is_some() => {
+                let Some(sender) = requests.as_ref() else {
+                    continue;
+                };
+                if ticks == actor_config.ticks {
+                    requests = None;
+                    continue;
+                }
+                ticks += 1;
+
+                // Ratchets heal with every message, so commit ticks send too
+                let messages = sessions
+                    .iter_mut()
+                    .map(|(&peer, session)| (peer, session.ratchet_encrypt(&message, &[])))
+                    .collect();
+                sender.send(Request {
+                    sender: client,
+                    sent_at: Instant::now(),
+                    upload: Upload::Ratchet(messages),
+                })
+                .map_err(|_| anyhow!("Delivery service stopped"))?;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+fn channels(
+    members: usize,
+) -> (
+    Vec<mpsc::UnboundedSender<Delivery>>,
+    Vec<mpsc::UnboundedReceiver<Delivery>>,
+) {
+    (0..members).map(|_| mpsc::unbounded_channel()).unzip()
+}
+
+async fn collect(
+    start: Instant,
+    server: tokio::task::JoinHandle<DeliveryStats>,
+    members: Vec<tokio::task::JoinHandle<Result<MemberStats>>>,
+) -> Result<ActorReport> {
+    let mut report = ActorReport::default();
+    for member in members {
+        let stats = member.await??;
+        report.delivered += stats.delivered;
+        report.undecryptable += stats.undecryptable;
+        report.commits_accepted += stats.commits_accepted;
+        report.commits_rejected += stats.commits_rejected;
+        report.total_queueing_delay += stats.total_queueing_delay;
+        report.max_queueing_delay = report.max_queueing_delay.max(stats.max_queueing_delay);
+    }
+    report.delivery = server.await?;
+    report.wall_time = start.elapsed();
+    Ok(report)
+}
+
+/// Runs every member of an MLS group as its own task, all talking to one
+/// delivery service task.
+pub async fn simulate_mls_actors(
+    bench_config: Arc<BenchConfig>,
+    actor_config: &ActorConfig,
+) -> Result<ActorReport> {
+    let mut key_service = KeyService::new();
+    key_service.generate(
+        &bench_config.ciphersuite,
+        &bench_config.provider,
+        actor_config.members - 1,
+    )?;
+    let (creator, member_states) =
+        create_bare_group_with_member_states(&bench_config, &key_service);
+    let group_id = creator.group_id().clone();
+    let clients: Vec<ClientId> = (0..actor_config.members).collect();
+    let mut delivery = DeliveryService::new();
+    delivery.register_group(group_id.clone(), &clients, creator.epoch().as_u64());
+
+    let states = std::iter::once(MemberState {
+        group: creator,
+        signer: bench_config.self_signer.clone(),
+    })
+    .chain(member_states);
+
+    let start = Instant::now();
+    let (request_sender, requests) = mpsc::unbounded_channel();
+    let (inbox_senders, inboxes) = channels(actor_config.members);
+    let server = tokio::spawn(run_server(group_id, delivery, requests, inbox_senders));
+    let members = states
+        .zip(inboxes)
+        .enumerate()
+        .map(|(client, (state, inbox))| {
+            tokio::spawn(run_mls_member(
+                bench_config.clone(),
+                actor_config.clone(),
+                client,
+                state,
+                request_sender.clone(),
+                inbox,
+            ))
+        })
+        .collect();
+    drop(request_sender);
+
+    collect(start, server, members).await
+}
+
+/// Same as [`simulate_mls_actors`] with a pairwise ratchet session between
+/// every two members.
+pub async fn simulate_ratchet_actors(actor_config: &ActorConfig) -> Result<ActorReport> {
+    let mut sessions: Vec<HashMap<ClientId, Ratchet<StaticSecret>>> =
+        (0..actor_config.members).map(|_| HashMap::new()).collect();
+    for i in 0..actor_config.members {
+        for j in i + 1..actor_config.members {
+            let (local, remote) = RatchetGroup::session_pair();
+            sessions[i].insert(j, local);
+            sessions[j].insert(i, remote);
+        }
+    }
+
+    let start = Instant::now();
+    let (request_sender, requests) = mpsc::unbounded_channel();
+    let (inbox_senders, inboxes) = channels(actor_config.members);
+    // Pairwise messages are addressed to members, not to a group
+    let server = tokio::spawn(run_server(
+        GroupId::from_slice(b"pairwise"),
+        DeliveryService::new(),
+        requests,
+        inbox_senders,
+    ));
+    let members = sessions
+        .into_iter()
+        .zip(inboxes)
+        .enumerate()
+        .map(|(client, (sessions, inbox))| {
+            tokio::spawn(run_ratchet_member(
+                actor_config.clone(),
+                client,
+                sessions,
+                request_sender.clone(),
+                inbox,
+            ))
+        })
+        .collect();
+    drop(request_sender);
+
+    collect(start, server, members).await
+}
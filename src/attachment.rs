@@ -0,0 +1,140 @@
+use anyhow::{bail, Result};
+use double_ratchet_2::aead::{decrypt, encrypt};
+use openmls::prelude::*;
+use openmls_rust_crypto::OpenMlsRustCrypto;
+
+use crate::mls::BenchConfig;
+use crate::ratchet::{catch_decrypt, generate_random_bytes, RatchetGroup, RatchetMessage};
+
+const ATTACHMENT_LABEL: &str = "attachment";
+const ID_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+// Authentication tag appended to every blob
+const TAG_LEN: usize = 16;
+
+/// Points at an encrypted attachment stored outside the group messages.
+/// Only this reference travels through the group.
+pub struct AttachmentReference {
+    pub id: [u8; ID_LEN],
+    pub nonce: [u8; NONCE_LEN],
+    /// Set when the key cannot be derived by the recipients themselves
+    pub key: Option<[u8; KEY_LEN]>,
+}
+
+impl AttachmentReference {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ID_LEN + NONCE_LEN + KEY_LEN);
+        bytes.extend_from_slice(&self.id);
+        bytes.extend_from_slice(&self.nonce);
+        if let Some(key) = self.key {
+            bytes.extend_from_slice(&key);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let key = match bytes.len() {
+            len if len == ID_LEN + NONCE_LEN => None,
+            len if len == ID_LEN + NONCE_LEN + KEY_LEN => {
+                Some(bytes[ID_LEN + NONCE_LEN..].try_into()?)
+            }
+            _ => bail!("Invalid attachment reference"),
+        };
+        Ok(Self {
+            id: bytes[..ID_LEN].try_into()?,
+            nonce: bytes[ID_LEN..ID_LEN + NONCE_LEN].try_into()?,
+            key,
+        })
+    }
+}
+
+/// Key for attachment `id` in the group's current epoch, derived from the
+/// exporter secret so every member can compute it.
+pub fn mls_attachment_key(
+    group: &MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    id: &[u8; ID_LEN],
+) -> Result<[u8; KEY_LEN]> {
+    let key = group.export_secret(provider, ATTACHMENT_LABEL, id, KEY_LEN)?;
+    Ok(key.as_slice().try_into()?)
+}
+
+/// Encrypts `data` once under an exporter-derived key, returning the blob to
+/// upload and the group message carrying its reference.
+pub fn send_mls_attachment(
+    group: &mut MlsGroup,
+    bench_config: &BenchConfig,
+    data: &[u8],
+) -> Result<(Vec<u8>, MlsMessageOut)> {
+    let id = generate_random_bytes::<ID_LEN>()?;
+    let key = mls_attachment_key(group, &bench_config.provider, &id)?;
+    let (blob, nonce) = encrypt(&key, data, &id);
+
+    let reference = AttachmentReference {
+        id,
+        nonce,
+        key: None,
+    };
+    let message = group.create_message(
+        &bench_config.provider,
+        &bench_config.self_signer,
+        &reference.to_bytes(),
+    )?;
+    Ok((blob, message))
+}
+
+// aead::decrypt panics on a wrong key or a tampered blob, so the error is
+// caught and returned instead
+fn decrypt_blob(
+    key: &[u8; KEY_LEN],
+    blob: &[u8],
+    reference: &AttachmentReference,
+) -> Result<Vec<u8>> {
+    if blob.len() < TAG_LEN {
+        bail!("Attachment is too short");
+    }
+    catch_decrypt(|| decrypt(key, blob, &reference.id, &reference.nonce))
+}
+
+/// Decrypts an attachment. Must be called in the epoch the reference was
+/// sent in, and fails if the blob does not decrypt under the derived key.
+pub fn open_mls_attachment(
+    group: &MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    reference: &[u8],
+    blob: &[u8],
+) -> Result<Vec<u8>> {
+    let reference = AttachmentReference::from_bytes(reference)?;
+    let key = mls_attachment_key(group, provider, &reference.id)?;
+    decrypt_blob(&key, blob, &reference)
+}
+
+/// Pairwise equivalent: the attachment is encrypted once under a fresh group
+/// key, as in the optimized scheme, and only the reference is sent to each
+/// member.
+pub fn send_ratchet_attachment(
+    ratchet_group: &mut RatchetGroup,
+    data: &[u8],
+) -> Result<(Vec<u8>, Vec<RatchetMessage>)> {
+    let id = generate_random_bytes::<ID_LEN>()?;
+    let key = generate_random_bytes::<KEY_LEN>()?;
+    let (blob, nonce) = encrypt(&key, data, &id);
+
+    let reference = AttachmentReference {
+        id,
+        nonce,
+        key: Some(key),
+    };
+    Ok((blob, ratchet_group.encrypt_message(&reference.to_bytes())))
+}
+
+/// Decrypts an attachment with the key carried in its reference. Fails if
+/// the blob does not decrypt under that key.
+pub fn open_ratchet_attachment(reference: &[u8], blob: &[u8]) -> Result<Vec<u8>> {
+    let reference = AttachmentReference::from_bytes(reference)?;
+    let Some(key) = reference.key else {
+        bail!("Attachment reference carries no key");
+    };
+    decrypt_blob(&key, blob, &reference)
+}
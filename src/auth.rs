@@ -0,0 +1,96 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+use openmls::prelude::*;
+
+/// Why an authentication service refused a member. Returned inside the
+/// `anyhow::Error` of the checks below, so callers can tell them apart.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthError {
+    UnknownSignatureKey,
+    /// The signature key is registered, but to a different credential
+    IdentityMismatch,
+    Revoked,
+    NotAMember,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::UnknownSignatureKey => write!(f, "Unknown signature key"),
+            AuthError::IdentityMismatch => {
+                write!(f, "Signature key is registered to a different identity")
+            }
+            AuthError::Revoked => write!(f, "Signature key has been revoked"),
+            AuthError::NotAMember => write!(f, "Sender is not a member of the group"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// The [`AuthError`] somewhere in the chain of `error`, if any.
+pub fn auth_error(error: &anyhow::Error) -> Option<&AuthError> {
+    error.chain().find_map(|cause| cause.downcast_ref())
+}
+
+pub trait AuthenticationService {
+    /// Checks that `signature_key` belongs to the identity in `credential`.
+    fn authenticate(&self, credential: &Credential, signature_key: &[u8]) -> Result<()>;
+}
+
+pub struct AllowAll;
+
+impl AuthenticationService for AllowAll {
+    fn authenticate(&self, _credential: &Credential, _signature_key: &[u8]) -> Result<()> { Ok(()) }
+}
+
+pub fn check_key_package(
+    auth: &impl AuthenticationService,
+    key_package: &KeyPackage,
+) -> Result<()> {
+    check_leaf_node(auth, key_package.leaf_node())
+}
+
+pub fn check_leaf_node(auth: &impl AuthenticationService, leaf_node: &LeafNode) -> Result<()> {
+    auth.authenticate(leaf_node.credential(), leaf_node.signature_key().as_slice())
+}
+
+/// Checks every member of a group, e.g. right after joining it from a Welcome.
+pub fn check_members(auth: &impl AuthenticationService, group: &MlsGroup) -> Result<()> {
+    for member in group.members() {
+        auth.authenticate(&member.credential, &member.signature_key)?;
+    }
+    Ok(())
+}
+
+pub fn check_sender(
+    auth: &impl AuthenticationService,
+    group: &MlsGroup,
+    sender: &Sender,
+) -> Result<()> {
+    let Sender::Member(leaf_index) = sender else {
+        return Ok(());
+    };
+    let Some(member) = group.members().find(|member| member.index == *leaf_index) else {
+        bail!(AuthError::NotAMember);
+    };
+    auth.authenticate(&member.credential, &member.signature_key)
+}
+
+/// Checks all leaves introduced or changed by a commit.
+pub fn check_staged_commit(
+    auth: &impl AuthenticationService,
+    staged_commit: &StagedCommit,
+) -> Result<()> {
+    for add in staged_commit.add_proposals() {
+        check_key_package(auth, add.add_proposal().key_package())?;
+    }
+    for update in staged_commit.update_proposals() {
+        check_leaf_node(auth, update.update_proposal().leaf_node())?;
+    }
+    if let Some(leaf_node) = staged_commit.update_path_leaf_node() {
+        check_leaf_node(auth, leaf_node)?;
+    }
+    Ok(())
+}
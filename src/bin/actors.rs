@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use openmls_test::{
+    actors::{simulate_mls_actors, simulate_ratchet_actors, ActorConfig, ActorReport},
+    mls::BenchConfig,
+};
+
+fn print_report(name: &str, report: &ActorReport) {
+    println!(
+        "  {}: {} delivered in {:?} ({:.0}/s), {} undecryptable, {} commits accepted, \
+         {} rejected, queueing delay mean {:?} max {:?}, {} bytes uploaded",
+        name,
+        report.delivered,
+        report.wall_time,
+        report.throughput(),
+        report.undecryptable,
+        report.commits_accepted,
+        report.commits_rejected,
+        report.mean_queueing_delay(),
+        report.max_queueing_delay,
+        report.delivery.uploaded_bytes
+    );
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    for members in [10, 50, 100] {
+        for tick_interval in [Duration::from_millis(100), Duration::from_millis(10)] {
+            let actor_config = ActorConfig {
+                members,
+                ticks: 20,
+                tick_interval,
+                commit_every: 10,
+                message_size: 1024,
+            };
+            println!("{} members, a tick every {:?}", members, tick_interval);
+            let bench_config = Arc::new(BenchConfig::default());
+            print_report(
+                "TreeKEM",
+                &simulate_mls_actors(bench_config, &actor_config).await?,
+            );
+            print_report(
+                "Pairwise Ratchet",
+                &simulate_ratchet_actors(&actor_config).await?,
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use anyhow::Result;
+use openmls_test::{
+    mls::BenchConfig,
+    offline::{mls_catch_up, optimized_catch_up, pairwise_catch_up, CatchUpReport},
+};
+
+const MESSAGE_SIZE: usize = 1024;
+
+fn print_report(name: &str, report: &CatchUpReport) {
+    println!(
+        "  {}: {} envelopes, {} bytes downloaded, processed in {:?}",
+        name, report.envelopes, report.downloaded_bytes, report.processing_time
+    );
+}
+
+fn main() -> Result<()> {
+    let config = BenchConfig::default();
+
+    for count in [2, 100, 1024] {
+        for commits in [0, 10, 100] {
+            for messages in [100, 1000] {
+                println!(
+                    "Group size {}, {} missed commits, {} missed messages",
+                    count, commits, messages
+                );
+                print_report(
+                    "TreeKEM",
+                    &mls_catch_up(&config, count, commits, messages, MESSAGE_SIZE)?,
+                );
+                print_report(
+                    "Pairwise Ratchet",
+                    &pairwise_catch_up(count, messages, MESSAGE_SIZE)?,
+                );
+                print_report(
+                    "Optimized Ratchet",
+                    &optimized_catch_up(count, messages, MESSAGE_SIZE)?,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use openmls_test::{
+    conflict::{simulate_concurrent_commits, OrderingPolicy, Sequencer},
+    key_service::KeyService,
+    mls::{create_bare_group_with_member_states, BenchConfig},
+};
+
+fn main() -> Result<()> {
+    let config = BenchConfig::default();
+
+    for count in [10, 100] {
+        for committers in [2, 5, 10] {
+            for policy in [OrderingPolicy::FirstArrival, OrderingPolicy::LowestLeaf] {
+                let mut key_service = KeyService::new();
+                key_service.generate(&config.ciphersuite, &config.provider, count)?;
+                let (_, mut member_states) =
+                    create_bare_group_with_member_states(&config, &key_service);
+
+                let mut sequencer = Sequencer::new(policy, 0);
+                let report = simulate_concurrent_commits(
+                    &config,
+                    &mut member_states,
+                    committers,
+                    &mut sequencer,
+                )?;
+
+                println!(
+                    "Group size {}, {} committers, {:?}: {} rounds, {} wasted commits \
+                     ({} bytes, {:?}), converged in {:?}",
+                    count,
+                    committers,
+                    policy,
+                    report.rounds,
+                    report.wasted_commits,
+                    report.wasted_bytes,
+                    report.wasted_time,
+                    report.convergence_time
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
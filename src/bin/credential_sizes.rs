@@ -0,0 +1,57 @@
+use anyhow::Result;
+use openmls::prelude::*;
+use openmls_test::{
+    credential::{create_keypackage, make_credential, make_x509_credential},
+    key_service::KeyService,
+    mls::{create_bare_group_with_members, BenchConfig},
+    x509::TestCa,
+};
+
+fn main() -> Result<()> {
+    let ca = TestCa::new("Test CA")?;
+
+    for count in [2, 100, 1024] {
+        for x509 in [false, true] {
+            let config = if x509 {
+                BenchConfig::with_x509(&ca)
+            } else {
+                BenchConfig::default()
+            };
+
+            let mut key_service = KeyService::new();
+            if x509 {
+                key_service.generate_x509(&config.ciphersuite, &config.provider, &ca, count)?;
+            } else {
+                key_service.generate(&config.ciphersuite, &config.provider, count)?;
+            }
+            let mut mls_group = create_bare_group_with_members(&config, &key_service);
+
+            let (credential, signer) = if x509 {
+                make_x509_credential(&config.ciphersuite, &config.provider, &ca, "Bob".into())?
+            } else {
+                make_credential(&config.ciphersuite, &config.provider, "Bob".into())?
+            };
+            let key_package =
+                create_keypackage(config.ciphersuite, &config.provider, credential, &signer)?;
+            let key_package_size = key_package.tls_serialized_len();
+
+            let (commit, welcome, _) =
+                mls_group.add_members(&config.provider, &config.self_signer, &[key_package])?;
+            mls_group.merge_pending_commit(&config.provider)?;
+            let tree_size = mls_group.export_ratchet_tree().tls_serialized_len();
+
+            println!(
+                "Group size {} ({}): key package {} bytes, commit {} bytes, welcome {} bytes, \
+                 ratchet tree {} bytes",
+                count,
+                if x509 { "X.509" } else { "Basic" },
+                key_package_size,
+                commit.tls_serialized_len(),
+                welcome.tls_serialized_len(),
+                tree_size
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use anyhow::Result;
+use openmls_test::{
+    devices::{mls_devices, ratchet_devices, DeviceConfig, DeviceReport},
+    mls::BenchConfig,
+};
+
+fn print_report(name: &str, report: &DeviceReport) {
+    println!(
+        "  {}: {} devices, add device {} bytes and {} sessions, remove device {} bytes, \
+         message {} bytes uploaded, {} bytes egress, {} queue writes, \
+         self sync {} bytes in {} writes, state per device {} bytes",
+        name,
+        report.devices,
+        report.add_device_uploaded,
+        report.add_device_sessions,
+        report.remove_device_uploaded,
+        report.message.uploaded,
+        report.message.egress,
+        report.message.queue_writes,
+        report.self_sync_bytes,
+        report.self_sync_writes,
+        report.state_per_device
+    );
+}
+
+fn main() -> Result<()> {
+    for users in [10, 100] {
+        // Our users average three devices
+        for devices_per_user in [1, 3] {
+            let config = DeviceConfig {
+                users,
+                devices_per_user,
+                message_size: 200,
+            };
+            println!("{} users with {} devices each", users, devices_per_user);
+            print_report("TreeKEM", &mls_devices(&BenchConfig::default(), &config)?);
+            print_report("Pairwise Ratchet", &ratchet_devices(&config, false)?);
+            print_report("Optimized Ratchet", &ratchet_devices(&config, true)?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use openmls_test::{
+    mls::BenchConfig,
+    multigroup::{simulate_many_groups, MultiGroupConfig},
+};
+
+fn main() -> Result<()> {
+    for groups in [100, 1000] {
+        let config = BenchConfig::default();
+        let multi_group_config = MultiGroupConfig {
+            groups,
+            sizes: vec![2, 3, 5, 10, 50, 100],
+            contacts: 500,
+            messages: 1000,
+            seed: 0,
+        };
+        let report = simulate_many_groups(&config, &multi_group_config)?;
+
+        println!("{} groups, {} memberships", groups, report.memberships);
+        println!(
+            "  TreeKEM: {} bytes of state ({} in the key store), loaded in {:?}, {:?} per \
+             incoming message",
+            report.mls_state, report.mls_key_store, report.mls_load_time, report.mls_dispatch_time
+        );
+        println!(
+            "  Pairwise Ratchet: {} peers, ~{} bytes of shared state (~{} bytes if kept per \
+             group), {:?} per incoming message",
+            report.peers,
+            report.shared_ratchet_state,
+            report.per_group_ratchet_state,
+            report.ratchet_dispatch_time
+        );
+    }
+
+    Ok(())
+}
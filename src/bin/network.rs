@@ -0,0 +1,83 @@
+use anyhow::Result;
+use openmls_test::{
+    mls::BenchConfig,
+    network::{
+        simulate_mls_network, simulate_ratchet_network, Latency, NetworkConfig, NetworkReport,
+        NetworkScenario, Partition,
+    },
+};
+
+fn print_report(name: &str, report: &NetworkReport) {
+    let converged: Vec<_> = report.convergence_times.iter().flatten().collect();
+    let mean = converged.iter().copied().sum::<u64>() as f64 / converged.len().max(1) as f64;
+    let max = converged.iter().copied().max().unwrap_or(&0);
+    println!(
+        "  {}: convergence mean {:.0} ms, max {} ms, {} never converged, \
+         {}/{} undecryptable ({:.2}%), {} retransmissions",
+        name,
+        mean,
+        max,
+        report.convergence_times.len() - converged.len(),
+        report.undecryptable,
+        report.received,
+        report.undecryptable_fraction() * 100.0,
+        report.retransmissions
+    );
+}
+
+fn main() -> Result<()> {
+    let networks = [
+        (
+            "LAN",
+            NetworkConfig {
+                latency: Latency::Constant(1),
+                ..Default::default()
+            },
+        ),
+        (
+            "Mobile",
+            NetworkConfig {
+                latency: Latency::Exponential(150),
+                loss_rate: 0.05,
+                ..Default::default()
+            },
+        ),
+        (
+            "Partitioned",
+            NetworkConfig {
+                latency: Latency::Uniform(20, 200),
+                loss_rate: 0.01,
+                partitions: vec![Partition {
+                    clients: (1..=5).collect(),
+                    start: 2_000,
+                    end: 8_000,
+                }],
+                ..Default::default()
+            },
+        ),
+    ];
+
+    for members in [10, 50] {
+        let scenario = NetworkScenario {
+            members,
+            joiners: 5,
+            commit_interval: 2_000,
+            message_interval: 500,
+            duration: 15_000,
+        };
+        for (name, network) in networks.iter() {
+            println!("{} members, {} network", members, name);
+            let config = BenchConfig::default();
+            print_report(
+                "TreeKEM",
+                &simulate_mls_network(&config, network, &scenario)?,
+            );
+            print_report(
+                "Pairwise Ratchet",
+                &simulate_ratchet_network(network, &scenario)?,
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use openmls::prelude::*;
+use openmls_test::{
+    key_service::KeyService,
+    mls::{create_bare_group_with_members, create_padded_message, BenchConfig},
+    padding::PaddingPolicy,
+    ratchet::{message_len, RatchetGroup},
+};
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+const SAMPLES: usize = 2000;
+
+// Mostly short texts, some paragraphs and the occasional pasted document
+fn message_size(rng: &mut ChaCha20Rng) -> usize {
+    let (min, max) = match rng.next_u32() % 100 {
+        0..=69 => (1, 200),
+        70..=94 => (200, 2_000),
+        _ => (2_000, 100_000),
+    };
+    min + rng.next_u32() as usize % (max - min)
+}
+
+fn report(name: &str, policy: PaddingPolicy, baseline: usize, lengths: &[usize]) {
+    let total: usize = lengths.iter().sum();
+    let distinct: HashSet<_> = lengths.iter().collect();
+    println!(
+        "{} {:?}: {} bytes, {:.1}% overhead, {} distinct lengths",
+        name,
+        policy,
+        total,
+        (total as f64 / baseline as f64 - 1.0) * 100.0,
+        distinct.len()
+    );
+}
+
+fn main() -> Result<()> {
+    let mut rng = ChaCha20Rng::seed_from_u64(0);
+    let sizes: Vec<usize> = (0..SAMPLES).map(|_| message_size(&mut rng)).collect();
+
+    let policies = [
+        PaddingPolicy::None,
+        PaddingPolicy::Block(32),
+        PaddingPolicy::Block(256),
+        PaddingPolicy::PowerOfTwo,
+    ];
+
+    let mut mls_baseline = 0;
+    let mut ratchet_baseline = 0;
+    for policy in policies {
+        let config = BenchConfig::with_padding(policy);
+        let mut key_service = KeyService::new();
+        key_service.generate(&config.ciphersuite, &config.provider, 1)?;
+        let mut mls_group = create_bare_group_with_members(&config, &key_service);
+
+        let mut ratchet_group = RatchetGroup::with_generated_members(1);
+        ratchet_group.set_padding(policy);
+
+        let mut mls_lengths = Vec::with_capacity(SAMPLES);
+        let mut ratchet_lengths = Vec::with_capacity(SAMPLES);
+        for &size in sizes.iter() {
+            let message = vec![1u8; size];
+            let mls_message = create_padded_message(&mut mls_group, &config, &message)?;
+            mls_lengths.push(mls_message.tls_serialized_len());
+
+            let ratchet_messages = ratchet_group.encrypt_message(&message);
+            ratchet_lengths.push(message_len(&ratchet_messages[0]));
+        }
+
+        if policy == PaddingPolicy::None {
+            mls_baseline = mls_lengths.iter().sum();
+            ratchet_baseline = ratchet_lengths.iter().sum();
+        }
+        report("TreeKEM", policy, mls_baseline, &mls_lengths);
+        report(
+            "Pairwise Ratchet",
+            policy,
+            ratchet_baseline,
+            &ratchet_lengths,
+        );
+    }
+
+    Ok(())
+}
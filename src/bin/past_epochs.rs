@@ -0,0 +1,37 @@
+use anyhow::Result;
+use openmls_test::{epochs::simulate_late_delivery, mls::BenchConfig};
+
+const COMMITS: usize = 5;
+const MESSAGES_PER_EPOCH: usize = 10;
+
+fn main() -> Result<()> {
+    for max_past_epochs in [0, 2, 5] {
+        for out_of_order_tolerance in [0, 5, 10] {
+            let config = BenchConfig::with_epoch_tolerance(max_past_epochs, out_of_order_tolerance);
+            let report = simulate_late_delivery(&config, COMMITS, MESSAGES_PER_EPOCH, 0)?;
+
+            println!(
+                "max_past_epochs {}, out_of_order_tolerance {}: {} decrypted, {} lost, \
+                 receiver state {} bytes",
+                max_past_epochs,
+                out_of_order_tolerance,
+                report.decrypted(),
+                report.lost(),
+                report.receiver_state_len
+            );
+            for epochs_behind in 0..=COMMITS as u64 {
+                let decrypted = report
+                    .outcomes
+                    .iter()
+                    .filter(|o| o.epochs_behind == epochs_behind && o.decrypted)
+                    .count();
+                println!(
+                    "  {} epochs behind: {}/{} decrypted",
+                    epochs_behind, decrypted, MESSAGES_PER_EPOCH
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use openmls::prelude::*;
+use openmls_test::{
+    credential::{create_keypackage, make_credential},
+    key_service::KeyService,
+    mls::{create_bare_group_with_joined_members, receive_message, BenchConfig},
+    proposals::{commit_pending, propose_add, propose_remove, propose_update},
+};
+
+fn main() -> Result<()> {
+    let config = BenchConfig::default();
+
+    for count in [2, 100, 1024] {
+        let mut key_service = KeyService::new();
+        key_service.generate(&config.ciphersuite, &config.provider, count)?;
+        let (_, mut member_states) =
+            create_bare_group_with_joined_members(&config, &key_service, 2);
+        let mut committer = member_states.remove(0);
+        let mut proposer = member_states.remove(0);
+
+        let (credential, signer) =
+            make_credential(&config.ciphersuite, &config.provider, "Bob".into())?;
+        let key_package =
+            create_keypackage(config.ciphersuite, &config.provider, credential, &signer)?;
+
+        let proposals = [
+            (
+                "add",
+                propose_add(
+                    &mut proposer.group,
+                    &config.provider,
+                    &proposer.signer,
+                    &key_package,
+                )?,
+            ),
+            (
+                "remove",
+                propose_remove(
+                    &mut proposer.group,
+                    &config.provider,
+                    &proposer.signer,
+                    LeafNodeIndex::new(0),
+                )?,
+            ),
+            (
+                "update",
+                propose_update(&mut proposer.group, &config.provider, &proposer.signer)?,
+            ),
+        ];
+        for (kind, proposal) in proposals.iter() {
+            receive_message(&mut committer.group, &config.provider, proposal)?;
+            println!(
+                "Group size {}: {} proposal {} bytes",
+                count,
+                kind,
+                proposal.tls_serialized_len()
+            );
+        }
+
+        let (commit, _) =
+            commit_pending(&mut committer.group, &config.provider, &committer.signer)?;
+        println!(
+            "Group size {}: commit of {} proposals {} bytes",
+            count,
+            proposals.len(),
+            commit.tls_serialized_len()
+        );
+    }
+
+    Ok(())
+}
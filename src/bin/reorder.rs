@@ -0,0 +1,39 @@
+use openmls_test::{
+    faults::{simulate_faulty_delivery, FaultConfig},
+    ratchet::{MAX_CACHED_KEYS, MAX_SKIPPED_KEYS},
+};
+
+const MEMBERS: usize = 10;
+const MESSAGES: usize = 2000;
+
+fn main() {
+    println!(
+        "Skipped-key limit: {} per gap, {} cached per session",
+        MAX_SKIPPED_KEYS, MAX_CACHED_KEYS
+    );
+    for reorder_window in [0, 10, 100] {
+        for drop_rate in [0.0, 0.01, 0.1] {
+            let faults = FaultConfig {
+                reorder_window,
+                drop_rate,
+                duplicate_rate: 0.05,
+                seed: 0,
+            };
+            let report = simulate_faulty_delivery(MEMBERS, MESSAGES, &faults);
+
+            println!(
+                "window {}, drop rate {}: {}/{} decrypted, {} dropped, {} duplicates rejected, \
+                 {} over the limit, at most {} skipped keys ({} bytes)",
+                reorder_window,
+                drop_rate,
+                report.decrypted,
+                report.sent,
+                report.dropped,
+                report.rejected_duplicates,
+                report.rejected_by_limit,
+                report.max_skipped_keys,
+                report.max_cache_bytes
+            );
+        }
+    }
+}
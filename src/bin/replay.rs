@@ -0,0 +1,39 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::{Context, Result};
+use openmls_test::{
+    mls::BenchConfig,
+    workload::{replay, MlsProtocol, Protocol, RatchetProtocol, Workload},
+};
+
+fn run(mut protocol: impl Protocol, workload: &Workload) -> Result<()> {
+    let report = replay(&mut protocol, workload)?;
+    println!(
+        "{}: {} events in {:?}, {} bytes uploaded, {} bytes downloaded, \
+         peak server storage {} bytes, client storage {} bytes (peak {})",
+        protocol.name(),
+        report.events,
+        report.cpu_time,
+        report.uploaded_bytes,
+        report.downloaded_bytes,
+        report.peak_server_storage,
+        report.final_client_storage,
+        report.peak_client_storage
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "traces/example.jsonl".into());
+    let file = File::open(&path).with_context(|| format!("Failed to open {}", path))?;
+    let workload = Workload::read(BufReader::new(file))?;
+
+    run(MlsProtocol::new(BenchConfig::default()), &workload)?;
+    run(RatchetProtocol::new(false), &workload)?;
+    run(RatchetProtocol::new(true), &workload)?;
+
+    Ok(())
+}
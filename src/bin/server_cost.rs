@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use openmls_test::{
+    cost::{mls_message_cost, project, ratchet_message_cost, MessageCost, Prices},
+    mls::BenchConfig,
+    synthetic::{generate_many, WorkloadConfig},
+    workload::{replay, MlsProtocol, Protocol, RatchetProtocol, ReplayReport, Workload},
+};
+
+const MESSAGE_SIZE: usize = 200;
+const GROUPS: usize = 3;
+// Recipients are expected to fetch within a day
+const RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn print_cost(name: &str, cost: MessageCost) {
+    println!(
+        "  {}: {} bytes uploaded, {} bytes egress, {} bytes stored, {} queue writes",
+        name, cost.uploaded, cost.egress, cost.stored, cost.queue_writes
+    );
+}
+
+fn projection<P: Protocol>(protocol: impl Fn() -> P, workloads: &[Workload]) -> Result<()> {
+    let mut name = "";
+    let mut sum = ReplayReport::default();
+    for workload in workloads {
+        let mut protocol = protocol();
+        name = protocol.name();
+        let report = replay(&mut protocol, workload)?;
+        sum.uploaded_bytes += report.uploaded_bytes;
+        sum.downloaded_bytes += report.downloaded_bytes;
+        sum.queue_writes += report.queue_writes;
+        sum.stored_bytes += report.stored_bytes;
+    }
+    let cost = project(&sum, &Prices::default(), RETENTION);
+    println!(
+        "  {}: ingress {:.6}, egress {:.6}, storage {:.6}, queue writes {:.6}, total {:.6}",
+        name,
+        cost.ingress,
+        cost.egress,
+        cost.storage,
+        cost.queue_writes,
+        cost.total()
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    for count in [2, 10, 100, 1000] {
+        println!("Group size {}, {} byte message", count, MESSAGE_SIZE);
+        print_cost(
+            "TreeKEM",
+            mls_message_cost(&BenchConfig::default(), count, MESSAGE_SIZE)?,
+        );
+        print_cost(
+            "Pairwise Ratchet",
+            ratchet_message_cost(count, MESSAGE_SIZE, false)?,
+        );
+        print_cost(
+            "Optimized Ratchet",
+            ratchet_message_cost(count, MESSAGE_SIZE, true)?,
+        );
+    }
+
+    let presets = [
+        (
+            "Large low-activity groups",
+            WorkloadConfig::large_low_activity(),
+        ),
+        ("Small chatty groups", WorkloadConfig::small_chatty()),
+    ];
+    for (name, config) in presets {
+        let workloads = generate_many(&config, GROUPS)?;
+        println!("{}, {} groups, default prices", name, GROUPS);
+        projection(|| MlsProtocol::new(BenchConfig::default()), &workloads)?;
+        projection(|| RatchetProtocol::new(false), &workloads)?;
+        projection(|| RatchetProtocol::new(true), &workloads)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use openmls_test::{
+    mls::BenchConfig,
+    state::{measure_mls_state, measure_ratchet_state, CountingAllocator},
+};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const CHECKPOINTS: [usize; 4] = [0, 10, 100, 1000];
+
+fn main() -> Result<()> {
+    for count in [2, 100, 1024] {
+        for loss_every in [0, 10] {
+            println!(
+                "Group size {}, {}",
+                count,
+                if loss_every == 0 {
+                    "no lost messages".to_string()
+                } else {
+                    format!("every {}th message lost", loss_every)
+                }
+            );
+
+            let config = BenchConfig::default();
+            for sample in measure_mls_state(&config, count, &CHECKPOINTS, loss_every)? {
+                println!(
+                    "  TreeKEM after {} messages: {} bytes saved, {} bytes in memory, \
+                     ratchet tree {} bytes, key store {} bytes in {} entries",
+                    sample.messages,
+                    sample.saved,
+                    sample.heap,
+                    sample.ratchet_tree,
+                    sample.key_store,
+                    sample.key_store_entries
+                );
+                for (component, len) in &sample.components {
+                    println!("    {}: {} bytes", component, len);
+                }
+            }
+            for sample in measure_ratchet_state(count, &CHECKPOINTS, loss_every)? {
+                println!(
+                    "  Pairwise Ratchet after {} messages: {} sessions, ~{} skipped keys, \
+                     ~{} bytes of state, {} bytes in memory for both ends",
+                    sample.messages,
+                    sample.sessions,
+                    sample.skipped_keys,
+                    sample.estimated,
+                    sample.heap
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
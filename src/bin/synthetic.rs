@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use openmls_test::{
+    mls::BenchConfig,
+    synthetic::{generate_many, WorkloadConfig},
+    workload::{replay, MlsProtocol, Protocol, RatchetProtocol, ReplayReport, Workload},
+};
+
+const GROUPS: usize = 3;
+
+fn total<P: Protocol>(protocol: impl Fn() -> P, workloads: &[Workload]) -> Result<()> {
+    let mut name = "";
+    let mut sum = ReplayReport::default();
+    for workload in workloads {
+        let mut protocol = protocol();
+        name = protocol.name();
+        let report = replay(&mut protocol, workload)?;
+        sum.events += report.events;
+        sum.cpu_time += report.cpu_time;
+        sum.uploaded_bytes += report.uploaded_bytes;
+        sum.downloaded_bytes += report.downloaded_bytes;
+        sum.queue_writes += report.queue_writes;
+        sum.stored_bytes += report.stored_bytes;
+        sum.peak_server_storage += report.peak_server_storage;
+        sum.final_client_storage += report.final_client_storage;
+    }
+    println!(
+        "  {}: {} events in {:?}, {} bytes uploaded, {} bytes downloaded, \
+         {} queue writes, peak server storage {} bytes, client storage {} bytes",
+        name,
+        sum.events,
+        sum.cpu_time,
+        sum.uploaded_bytes,
+        sum.downloaded_bytes,
+        sum.queue_writes,
+        sum.peak_server_storage,
+        sum.final_client_storage
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let presets = [
+        (
+            "Large low-activity groups",
+            "large_low_activity",
+            WorkloadConfig::large_low_activity(),
+        ),
+        (
+            "Small chatty groups",
+            "small_chatty",
+            WorkloadConfig::small_chatty(),
+        ),
+    ];
+
+    // Pass a directory to also write the first generated workload of each
+    // preset there, as `<preset>.jsonl`
+    let output = std::env::args().nth(1).map(PathBuf::from);
+
+    for (name, file_name, config) in presets {
+        let workloads = generate_many(&config, GROUPS)?;
+        if let Some(directory) = &output {
+            let path = directory.join(file_name).with_extension("jsonl");
+            workloads[0].write(BufWriter::new(File::create(path)?))?;
+        }
+
+        println!("{}, {} groups", name, GROUPS);
+        total(|| MlsProtocol::new(BenchConfig::default()), &workloads)?;
+        total(|| RatchetProtocol::new(false), &workloads)?;
+        total(|| RatchetProtocol::new(true), &workloads)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use openmls::prelude::*;
+use openmls_test::{
+    credential::{create_keypackage, make_credential},
+    inspection::Inspector,
+    key_service::KeyService,
+    mls::{create_bare_group_with_members, BenchConfig},
+};
+
+fn main() -> Result<()> {
+    let policies = [
+        ("PublicMessage", PURE_PLAINTEXT_WIRE_FORMAT_POLICY),
+        ("PrivateMessage", PURE_CIPHERTEXT_WIRE_FORMAT_POLICY),
+    ];
+
+    for count in [2, 100, 1024] {
+        for (name, policy) in policies {
+            let config = BenchConfig::with_wire_format_policy(policy);
+            let mut key_service = KeyService::new();
+            key_service.generate(&config.ciphersuite, &config.provider, count)?;
+            let mut mls_group = create_bare_group_with_members(&config, &key_service);
+
+            let group_info =
+                mls_group.export_group_info(&config.provider, &config.self_signer, false)?;
+            let mut inspector = Inspector::new(
+                &config.provider,
+                &group_info,
+                mls_group.export_ratchet_tree().into(),
+            )?;
+
+            let (credential, signer) =
+                make_credential(&config.ciphersuite, &config.provider, "Bob".into())?;
+            let key_package =
+                create_keypackage(config.ciphersuite, &config.provider, credential, &signer)?;
+            let (add, _, _) =
+                mls_group.add_members(&config.provider, &config.self_signer, &[key_package])?;
+            mls_group.merge_pending_commit(&config.provider)?;
+            let add_view = inspector.inspect(&config.provider, &add)?;
+
+            let (update, _, _) = mls_group.self_update(&config.provider, &config.self_signer)?;
+            mls_group.merge_pending_commit(&config.provider)?;
+            let update_view = inspector.inspect(&config.provider, &update)?;
+
+            let (remove, _, _) = mls_group.remove_members(
+                &config.provider,
+                &config.self_signer,
+                &[LeafNodeIndex::new(1)],
+            )?;
+            mls_group.merge_pending_commit(&config.provider)?;
+            let remove_view = inspector.inspect(&config.provider, &remove)?;
+
+            println!(
+                "Group size {} ({}): add {} bytes, update {} bytes, remove {} bytes",
+                count,
+                name,
+                add.tls_serialized_len(),
+                update.tls_serialized_len(),
+                remove.tls_serialized_len()
+            );
+            println!("  Server sees add: {:?}", add_view);
+            println!("  Server sees update: {:?}", update_view);
+            println!("  Server sees remove: {:?}", remove_view);
+        }
+    }
+
+    Ok(())
+}
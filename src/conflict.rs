@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use openmls::prelude::*;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+use crate::delivery::{ClientId, DeliveryService, Envelope};
+use crate::mls::{receive_message, BenchConfig, MemberState};
+
+#[derive(Clone, Copy, Debug)]
+pub enum OrderingPolicy {
+    /// The commit reaching the delivery service first wins. Arrival order is
+    /// drawn at random.
+    FirstArrival,
+    /// The commit from the lowest leaf index wins.
+    LowestLeaf,
+}
+
+/// Delivery-service side sequencing of commits sent in the same epoch.
+pub struct Sequencer {
+    policy: OrderingPolicy,
+    rng: ChaCha20Rng,
+}
+
+impl Sequencer {
+    pub fn new(policy: OrderingPolicy, seed: u64) -> Self {
+        Self {
+            policy,
+            rng: ChaCha20Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Picks the winner among commits sent by the given leaves.
+    pub fn pick(&mut self, senders: &[LeafNodeIndex]) -> usize {
+        match self.policy {
+            OrderingPolicy::FirstArrival => self.rng.next_u64() as usize % senders.len(),
+            OrderingPolicy::LowestLeaf => (0..senders.len())
+                .min_by_key(|&i| senders[i].u32())
+                .expect("No commits to sequence"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConflictReport {
+    /// Epochs needed until every committer got its commit accepted
+    pub rounds: usize,
+    pub wasted_commits: usize,
+    pub wasted_bytes: usize,
+    /// Time spent creating commits that were later discarded
+    pub wasted_time: Duration,
+    pub convergence_time: Duration,
+}
+
+/// Lets the first `committers` members commit an update in the same epoch,
+/// repeating until all of them succeeded. Each round the sequencer accepts a
+/// single commit; all other committers discard theirs, process the winner and
+/// retry in the next epoch.
+pub fn simulate_concurrent_commits(
+    bench_config: &BenchConfig,
+    member_states: &mut [MemberState],
+    committers: usize,
+    sequencer: &mut Sequencer,
+) -> Result<ConflictReport> {
+    let mut report = ConflictReport::default();
+    let mut pending: Vec<usize> = (0..committers.min(member_states.len())).collect();
+    let start = Instant::now();
+
+    let Some(first) = member_states.first() else {
+        return Ok(report);
+    };
+    let group_id = first.group.group_id().clone();
+    let clients: Vec<ClientId> = (0..member_states.len()).collect();
+    let mut delivery = DeliveryService::new();
+    delivery.register_group(group_id.clone(), &clients, first.group.epoch().as_u64());
+
+    while !pending.is_empty() {
+        report.rounds += 1;
+
+        let mut commits = Vec::with_capacity(pending.len());
+        for &i in pending.iter() {
+            let member = &mut member_states[i];
+            let epoch = member.group.epoch().as_u64();
+            let commit_start = Instant::now();
+            let (commit, _, _) = member
+                .group
+                .self_update(&bench_config.provider, &member.signer)?;
+            commits.push((epoch, commit, commit_start.elapsed()));
+        }
+
+        // The sequencer decides which commit reaches the delivery service
+        // first, the others arrive after it and are rejected
+        let senders: Vec<_> = pending
+            .iter()
+            .map(|&i| member_states[i].group.own_leaf_index())
+            .collect();
+        let winner = sequencer.pick(&senders);
+        let winner_index = pending.remove(winner);
+        let (epoch, winning_commit, _) = commits.remove(winner);
+        delivery.submit_commit(&group_id, winner_index, epoch, &winning_commit)?;
+        member_states[winner_index]
+            .group
+            .merge_pending_commit(&bench_config.provider)?;
+
+        for (&i, (epoch, commit, elapsed)) in pending.iter().zip(commits) {
+            if delivery.submit_commit(&group_id, i, epoch, &commit).is_ok() {
+                bail!("Delivery service accepted a second commit for one epoch");
+            }
+            member_states[i].group.clear_pending_commit();
+            report.wasted_commits += 1;
+            report.wasted_time += elapsed;
+        }
+
+        for (i, member) in member_states.iter_mut().enumerate() {
+            for envelope in delivery.fetch(i) {
+                let Envelope::Mls { message, .. } = envelope else {
+                    bail!("Not an MLS message");
+                };
+                receive_message(&mut member.group, &bench_config.provider, &message)?;
+            }
+        }
+    }
+
+    report.wasted_bytes = delivery.stats().rejected_bytes;
+    report.convergence_time = start.elapsed();
+    Ok(report)
+}
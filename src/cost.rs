@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+use crate::delivery::{ClientId, DeliveryService, DeliveryStats};
+use crate::key_service::KeyService;
+use crate::mls::{create_bare_group_with_members, BenchConfig, CREATOR};
+use crate::ratchet::RatchetGroup;
+use crate::workload::ReplayReport;
+
+const GB: f64 = 1_000_000_000.0;
+const MONTH: f64 = 30.0 * 24.0 * 60.0 * 60.0;
+
+/// Server side cost of delivering one application message to a group.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessageCost {
+    pub uploaded: usize,
+    /// Bytes the server sends out to all recipients
+    pub egress: usize,
+    /// Bytes held in mailboxes until every recipient fetched, a shared
+    /// payload counted once
+    pub stored: usize,
+    pub queue_writes: usize,
+}
+
+impl MessageCost {
+    pub(crate) fn from_stats(stats: &DeliveryStats) -> Self {
+        MessageCost {
+            uploaded: stats.uploaded_bytes,
+            egress: stats.delivered_bytes,
+            stored: stats.peak_stored_bytes,
+            queue_writes: stats.queue_writes,
+        }
+    }
+}
+
+// Every member but the creator receives
+fn recipients(group_size: usize) -> Result<Vec<ClientId>> {
+    if group_size == 0 {
+        bail!("A group needs at least one member");
+    }
+    Ok((CREATOR + 1..group_size).collect())
+}
+
+fn fetch_all(delivery: &mut DeliveryService, recipients: &[ClientId]) {
+    for &recipient in recipients {
+        delivery.fetch(recipient);
+    }
+}
+
+/// One message of `message_size` bytes, uploaded once and fanned out by the
+/// server.
+pub fn mls_message_cost(
+    bench_config: &BenchConfig,
+    group_size: usize,
+    message_size: usize,
+) -> Result<MessageCost> {
+    let recipients = recipients(group_size)?;
+    let mut key_service = KeyService::new();
+    key_service.generate(
+        &bench_config.ciphersuite,
+        &bench_config.provider,
+        recipients.len(),
+    )?;
+    let mut group = create_bare_group_with_members(bench_config, &key_service);
+    let mut members = recipients.clone();
+    members.push(CREATOR);
+
+    let mut delivery = DeliveryService::new();
+    delivery.register_group(group.group_id().clone(), &members, group.epoch().as_u64());
+    let message = group.create_message(
+        &bench_config.provider,
+        &bench_config.self_signer,
+        &vec![0u8; message_size],
+    )?;
+    delivery.send(group.group_id(), CREATOR, &message)?;
+    fetch_all(&mut delivery, &recipients);
+
+    Ok(MessageCost::from_stats(delivery.stats()))
+}
+
+/// Same as [`mls_message_cost`] with one ratchet message per recipient, or
+/// with `optimized` one payload stored once and a key per recipient, two
+/// queue writes per recipient.
+pub fn ratchet_message_cost(
+    group_size: usize,
+    message_size: usize,
+    optimized: bool,
+) -> Result<MessageCost> {
+    let recipients = recipients(group_size)?;
+    let mut group = RatchetGroup::with_generated_members(recipients.len());
+    let message = vec![0u8; message_size];
+
+    let mut delivery = DeliveryService::new();
+    if optimized {
+        let (ciphertext, nonce, keys) = group.encrypt_message_efficiently(&message);
+        delivery.send_payload(CREATOR, &ciphertext, nonce, &recipients);
+        delivery.send_ratchet(CREATOR, recipients.iter().copied().zip(keys).collect());
+    } else {
+        let messages = group.encrypt_message(&message);
+        delivery.send_ratchet(CREATOR, recipients.iter().copied().zip(messages).collect());
+    }
+    fetch_all(&mut delivery, &recipients);
+
+    Ok(MessageCost::from_stats(delivery.stats()))
+}
+
+/// Prices in the unit of your choice, per GB or per million writes.
+#[derive(Clone, Copy, Debug)]
+pub struct Prices {
+    pub ingress_per_gb: f64,
+    pub egress_per_gb: f64,
+    pub storage_per_gb_month: f64,
+    pub per_million_queue_writes: f64,
+}
+
+impl Default for Prices {
+    /// Roughly the list prices of a large cloud provider.
+    fn default() -> Self {
+        Prices {
+            ingress_per_gb: 0.0,
+            egress_per_gb: 0.09,
+            storage_per_gb_month: 0.023,
+            per_million_queue_writes: 0.4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CostProjection {
+    pub ingress: f64,
+    pub egress: f64,
+    pub storage: f64,
+    pub queue_writes: f64,
+}
+
+impl CostProjection {
+    pub fn total(&self) -> f64 { self.ingress + self.egress + self.storage + self.queue_writes }
+}
+
+/// Projects the server cost of a workload replayed through the delivery
+/// service. Every stored byte is assumed to sit in a mailbox for `retention`
+/// before being fetched.
+pub fn project(report: &ReplayReport, prices: &Prices, retention: Duration) -> CostProjection {
+    let uploaded = report.uploaded_bytes as f64 / GB;
+    let downloaded = report.downloaded_bytes as f64 / GB;
+    let stored = report.stored_bytes as f64 / GB;
+    CostProjection {
+        ingress: uploaded * prices.ingress_per_gb,
+        egress: downloaded * prices.egress_per_gb,
+        storage: stored * retention.as_secs_f64() / MONTH * prices.storage_per_gb_month,
+        queue_writes: report.queue_writes as f64 / 1_000_000.0 * prices.per_million_queue_writes,
+    }
+}
@@ -1,15 +1,78 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use ed25519_dalek::SigningKey;
 use openmls::prelude::*;
 use openmls_basic_credential::SignatureKeyPair;
+use rand_chacha::rand_core::OsRng;
+
+use crate::x509::{encode_chain, TestCa};
 
 pub fn make_credential(
     ciphersuite: &Ciphersuite,
     provider: &impl OpenMlsCryptoProvider,
     name: String,
 ) -> Result<(CredentialWithKey, SignatureKeyPair)> {
-    let me = Credential::new(name.into(), CredentialType::Basic)?;
-
     let sign_keys = SignatureKeyPair::new(ciphersuite.signature_algorithm())?;
+    store_credential(provider, name.into(), sign_keys)
+}
+
+/// Creates a credential whose signing key is the Ed25519 key `seed`.
+///
+/// Fails if `ciphersuite` doesn't sign with Ed25519, as no other scheme can
+/// be derived from a seed here.
+pub fn make_seeded_credential(
+    ciphersuite: &Ciphersuite,
+    provider: &impl OpenMlsCryptoProvider,
+    name: String,
+    seed: [u8; 32],
+) -> Result<(CredentialWithKey, SignatureKeyPair)> {
+    if ciphersuite.signature_algorithm() != SignatureScheme::ED25519 {
+        bail!("Seeded credentials require an Ed25519 ciphersuite");
+    }
+
+    let signing_key = SigningKey::from_bytes(&seed);
+    store_credential(provider, name.into(), ed25519_key_pair(&signing_key))
+}
+
+/// Creates a credential carrying a certificate chain issued by `ca`, as an
+/// approximation of an X.509 credential.
+///
+/// openmls 0.5 refuses to construct X.509 credentials, so this is a Basic
+/// credential whose identity is the TLS-encoded chain. On the wire it is one
+/// length prefix (1 to 4 bytes) larger than an X.509 credential holding the
+/// same chain. The identity is the chain rather than `name`, which is only
+/// the common name of the leaf certificate; use
+/// [`crate::x509::credential_chain`] to get the certificates back.
+pub fn make_x509_credential(
+    ciphersuite: &Ciphersuite,
+    provider: &impl OpenMlsCryptoProvider,
+    ca: &TestCa,
+    name: String,
+) -> Result<(CredentialWithKey, SignatureKeyPair)> {
+    if ciphersuite.signature_algorithm() != SignatureScheme::ED25519 {
+        bail!("X.509 credentials require an Ed25519 ciphersuite");
+    }
+
+    let signing_key = SigningKey::generate(&mut OsRng);
+    let leaf = ca.issue(&name, &signing_key)?;
+    let chain = encode_chain(&[leaf, ca.certificate_der().to_vec()])?;
+    store_credential(provider, chain, ed25519_key_pair(&signing_key))
+}
+
+fn ed25519_key_pair(signing_key: &SigningKey) -> SignatureKeyPair {
+    SignatureKeyPair::from_raw(
+        SignatureScheme::ED25519,
+        signing_key.to_bytes().to_vec(),
+        signing_key.verifying_key().to_bytes().to_vec(),
+    )
+}
+
+fn store_credential(
+    provider: &impl OpenMlsCryptoProvider,
+    identity: Vec<u8>,
+    sign_keys: SignatureKeyPair,
+) -> Result<(CredentialWithKey, SignatureKeyPair)> {
+    let me = Credential::new(identity, CredentialType::Basic)?;
+
     sign_keys
         .store(provider.key_store())
         .map_err(|_| anyhow!("Credential generation failed"))?;
@@ -39,3 +102,23 @@ pub fn create_keypackage(
 
     Ok(key_package)
 }
+
+pub fn create_keypackage_with_lifetime(
+    ciphersuite: Ciphersuite,
+    provider: &impl OpenMlsCryptoProvider,
+    credential_with_key: CredentialWithKey,
+    signer: &SignatureKeyPair,
+    lifetime: u64,
+) -> Result<KeyPackage> {
+    let key_package = KeyPackage::builder()
+        .key_package_lifetime(Lifetime::new(lifetime))
+        .build(
+            CryptoConfig::with_default_version(ciphersuite),
+            provider,
+            signer,
+            credential_with_key,
+        )
+        .map_err(|_| anyhow!("Keypackage building failed"))?;
+
+    Ok(key_package)
+}
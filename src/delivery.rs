@@ -0,0 +1,318 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{bail, Result};
+use openmls::prelude::*;
+use openmls::treesync::RatchetTreeIn;
+
+use crate::ratchet::{message_len, RatchetMessage};
+
+/// Index of a client in a simulation.
+pub type ClientId = usize;
+
+pub enum Envelope {
+    Welcome {
+        welcome: MlsMessageOut,
+        ratchet_tree: RatchetTreeIn,
+    },
+    Mls {
+        group_id: GroupId,
+        message: MlsMessageOut,
+    },
+    Ratchet {
+        sender: ClientId,
+        message: RatchetMessage,
+    },
+    /// Ciphertext shared by all recipients, as sent by the optimized ratchet
+    /// scheme next to the per-member key envelopes. The server stores it once,
+    /// every mailbox only holds a reference to it.
+    Payload {
+        id: u64,
+        sender: ClientId,
+        ciphertext: Vec<u8>,
+        nonce: [u8; 12],
+    },
+}
+
+impl Envelope {
+    pub fn len(&self) -> usize {
+        match self {
+            Envelope::Welcome {
+                welcome,
+                ratchet_tree,
+            } => welcome.tls_serialized_len() + ratchet_tree.tls_serialized_len(),
+            Envelope::Mls { message, .. } => message.tls_serialized_len(),
+            Envelope::Ratchet { message, .. } => message_len(message),
+            Envelope::Payload {
+                ciphertext, nonce, ..
+            } => ciphertext.len() + nonce.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DeliveryStats {
+    /// Bytes received from senders, each envelope counted once
+    pub uploaded_bytes: usize,
+    /// Bytes handed out to recipients
+    pub delivered_bytes: usize,
+    /// Bytes currently waiting in mailboxes, shared payloads counted once
+    pub stored_bytes: usize,
+    pub peak_stored_bytes: usize,
+    /// Bytes ever written to storage, shared payloads counted once
+    pub written_bytes: usize,
+    /// Envelopes written to a mailbox, one per recipient
+    pub queue_writes: usize,
+    pub rejected_commits: usize,
+    pub rejected_bytes: usize,
+}
+
+struct GroupState {
+    members: Vec<ClientId>,
+    epoch: u64,
+}
+
+/// In-process server model. Every client has a mailbox, MLS messages are
+/// fanned out to the members of their group and commits are sequenced so
+/// only the first commit of each epoch is accepted.
+///
+/// Group setup and the simulations route their traffic through it. The
+/// protocol helpers of [`crate::proposals`] and [`crate::psk`] only return
+/// the messages they create, for the caller to deliver.
+#[derive(Default)]
+pub struct DeliveryService {
+    groups: HashMap<GroupId, GroupState>,
+    mailboxes: HashMap<ClientId, VecDeque<Envelope>>,
+    /// Length and mailboxes still referencing each shared payload
+    payloads: HashMap<u64, (usize, usize)>,
+    next_payload: u64,
+    stats: DeliveryStats,
+}
+
+impl DeliveryService {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn stats(&self) -> &DeliveryStats { &self.stats }
+
+    /// Starts sequencing `group_id`, currently in `epoch`.
+    pub fn register_group(&mut self, group_id: GroupId, members: &[ClientId], epoch: u64) {
+        self.groups.insert(
+            group_id,
+            GroupState {
+                members: members.to_vec(),
+                epoch,
+            },
+        );
+    }
+
+    pub fn add_to_group(&mut self, group_id: &GroupId, client: ClientId) -> Result<()> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            bail!("Unknown group");
+        };
+        if !group.members.contains(&client) {
+            group.members.push(client);
+        }
+        Ok(())
+    }
+
+    pub fn remove_from_group(&mut self, group_id: &GroupId, client: ClientId) -> Result<()> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            bail!("Unknown group");
+        };
+        group.members.retain(|&member| member != client);
+        Ok(())
+    }
+
+    pub fn epoch(&self, group_id: &GroupId) -> Option<u64> {
+        self.groups.get(group_id).map(|group| group.epoch)
+    }
+
+    fn store(&mut self, len: usize) {
+        self.stats.stored_bytes += len;
+        self.stats.written_bytes += len;
+        self.stats.peak_stored_bytes = self.stats.peak_stored_bytes.max(self.stats.stored_bytes);
+    }
+
+    fn deliver(&mut self, recipient: ClientId, envelope: Envelope) {
+        // Shared payloads are stored once by `send_payload`
+        if !matches!(envelope, Envelope::Payload { .. }) {
+            self.store(envelope.len());
+        }
+        self.stats.queue_writes += 1;
+        self.mailboxes
+            .entry(recipient)
+            .or_default()
+            .push_back(envelope);
+    }
+
+    fn fan_out(
+        &mut self,
+        group_id: &GroupId,
+        sender: ClientId,
+        message: &MlsMessageOut,
+    ) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            bail!("Unknown group");
+        };
+        let recipients: Vec<_> = group
+            .members
+            .iter()
+            .copied()
+            .filter(|&member| member != sender)
+            .collect();
+        self.stats.uploaded_bytes += message.tls_serialized_len();
+        for recipient in recipients {
+            self.deliver(
+                recipient,
+                Envelope::Mls {
+                    group_id: group_id.clone(),
+                    message: message.clone(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    pub fn send_welcome(
+        &mut self,
+        welcome: &MlsMessageOut,
+        ratchet_tree: RatchetTreeIn,
+        recipients: &[ClientId],
+    ) {
+        self.stats.uploaded_bytes +=
+            welcome.tls_serialized_len() + ratchet_tree.tls_serialized_len();
+        for &recipient in recipients {
+            self.deliver(
+                recipient,
+                Envelope::Welcome {
+                    welcome: welcome.clone(),
+                    ratchet_tree: ratchet_tree.clone(),
+                },
+            );
+        }
+    }
+
+    /// Fans out an application message or proposal to the rest of the group.
+    pub fn send(
+        &mut self,
+        group_id: &GroupId,
+        sender: ClientId,
+        message: &MlsMessageOut,
+    ) -> Result<()> {
+        self.fan_out(group_id, sender, message)
+    }
+
+    /// Accepts `commit` if it was created in the current epoch of the group
+    /// and fans it out. Later commits for the same epoch are rejected, their
+    /// senders have to process the accepted one and retry.
+    pub fn submit_commit(
+        &mut self,
+        group_id: &GroupId,
+        sender: ClientId,
+        epoch: u64,
+        commit: &MlsMessageOut,
+    ) -> Result<()> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            bail!("Unknown group");
+        };
+        if group.epoch != epoch {
+            self.stats.rejected_commits += 1;
+            self.stats.rejected_bytes += commit.tls_serialized_len();
+            bail!(
+                "Commit for epoch {} while the group is in epoch {}",
+                epoch,
+                group.epoch
+            );
+        }
+        group.epoch += 1;
+        self.fan_out(group_id, sender, commit)
+    }
+
+    /// Delivers pairwise ratchet messages, each to its own recipient.
+    pub fn send_ratchet(&mut self, sender: ClientId, messages: Vec<(ClientId, RatchetMessage)>) {
+        for (recipient, message) in messages {
+            self.stats.uploaded_bytes += message_len(&message);
+            self.deliver(recipient, Envelope::Ratchet { sender, message });
+        }
+    }
+
+    /// Stores a ciphertext uploaded once and puts a reference to it in the
+    /// mailbox of every recipient.
+    pub fn send_payload(
+        &mut self,
+        sender: ClientId,
+        ciphertext: &[u8],
+        nonce: [u8; 12],
+        recipients: &[ClientId],
+    ) {
+        if recipients.is_empty() {
+            return;
+        }
+        let len = ciphertext.len() + nonce.len();
+        let id = self.next_payload;
+        self.next_payload += 1;
+        self.stats.uploaded_bytes += len;
+        self.store(len);
+        self.payloads.insert(id, (len, recipients.len()));
+        for &recipient in recipients {
+            self.deliver(
+                recipient,
+                Envelope::Payload {
+                    id,
+                    sender,
+                    ciphertext: ciphertext.to_vec(),
+                    nonce,
+                },
+            );
+        }
+    }
+
+    /// Hands out and clears everything waiting for `client`.
+    pub fn fetch(&mut self, client: ClientId) -> Vec<Envelope> {
+        let envelopes: Vec<_> = self
+            .mailboxes
+            .get_mut(&client)
+            .map(|mailbox| mailbox.drain(..).collect())
+            .unwrap_or_default();
+        for envelope in &envelopes {
+            self.stats.delivered_bytes += envelope.len();
+            self.release(envelope);
+        }
+        envelopes
+    }
+
+    /// Clears the mailbox of `client` without delivering it, as when a
+    /// member leaves before fetching.
+    pub fn discard(&mut self, client: ClientId) {
+        let envelopes: Vec<_> = self
+            .mailboxes
+            .get_mut(&client)
+            .map(|mailbox| mailbox.drain(..).collect())
+            .unwrap_or_default();
+        for envelope in &envelopes {
+            self.release(envelope);
+        }
+    }
+
+    // Frees the storage of an envelope leaving a mailbox, shared payloads once
+    // the last reference is gone
+    fn release(&mut self, envelope: &Envelope) {
+        let Envelope::Payload { id, .. } = envelope else {
+            self.stats.stored_bytes -= envelope.len();
+            return;
+        };
+        let Some((len, references)) = self.payloads.get_mut(id) else {
+            return;
+        };
+        *references -= 1;
+        if *references == 0 {
+            self.stats.stored_bytes -= *len;
+            self.payloads.remove(id);
+        }
+    }
+
+    pub fn pending(&self, client: ClientId) -> usize {
+        self.mailboxes.get(&client).map_or(0, VecDeque::len)
+    }
+}
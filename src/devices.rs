@@ -0,0 +1,245 @@
+use anyhow::{bail, Result};
+use double_ratchet_2::aead::decrypt;
+use openmls::prelude::*;
+use openmls::treesync::RatchetTreeIn;
+
+use crate::cost::MessageCost;
+use crate::delivery::{ClientId, DeliveryService, Envelope};
+use crate::key_service::{device_user, unix_time, KeyService};
+use crate::mls::{
+    add_member_from_key_service, add_members_checked, create_bare_group_with_members,
+    join_from_welcome, receive_message, saved_state_len, BenchConfig, CREATOR,
+};
+use crate::ratchet::{message_len, RatchetGroup};
+
+// User that gets a new device and loses it again
+const CHANGING_USER: &[u8] = b"Member 1";
+// Size of the instruction announcing a device over pairwise sessions, as in
+// the remove benchmark
+const INSTRUCTION_LEN: usize = 512;
+
+#[derive(Clone, Debug)]
+pub struct DeviceConfig {
+    /// Users in the group, including the creator
+    pub users: usize,
+    pub devices_per_user: usize,
+    pub message_size: usize,
+}
+
+impl DeviceConfig {
+    pub fn devices(&self) -> usize { self.users * self.devices_per_user }
+
+    fn check(&self) -> Result<()> {
+        if self.users < 2 || self.devices_per_user == 0 {
+            bail!("A group needs at least two users with a device each");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DeviceReport {
+    /// Devices in the group, each an MLS leaf or ratchet endpoint
+    pub devices: usize,
+    /// Bytes uploaded to add a device to a user already in the group
+    pub add_device_uploaded: usize,
+    pub remove_device_uploaded: usize,
+    /// Sessions set up between the new device and the devices in the group
+    pub add_device_sessions: usize,
+    /// One application message from a device to everyone else
+    pub message: MessageCost,
+    /// Part of the message egress fetched and decrypted by the sender's own
+    /// other devices
+    pub self_sync_bytes: usize,
+    pub self_sync_writes: usize,
+    /// State kept by a single device for the group
+    pub state_per_device: usize,
+}
+
+/// Every device is its own leaf, so sending to the other devices of the
+/// same user costs the server a queue write but the sender nothing extra.
+/// The creator's own devices are told apart by the user in their
+/// credentials.
+pub fn mls_devices(bench_config: &BenchConfig, config: &DeviceConfig) -> Result<DeviceReport> {
+    config.check()?;
+    let creator_user = device_user(bench_config.self_credential.credential.identity()).to_vec();
+    let mut key_service = KeyService::new();
+    key_service.generate_devices(
+        &bench_config.ciphersuite,
+        &bench_config.provider,
+        config.users - 1,
+        config.devices_per_user,
+    )?;
+    let mut group = create_bare_group_with_members(bench_config, &key_service);
+
+    // The creator's first device is the group creator itself, the others
+    // join from one Welcome to receive what it sends
+    let now = unix_time();
+    let mut key_packages = Vec::new();
+    for _ in 1..config.devices_per_user {
+        let device = key_service.add_device(
+            &bench_config.ciphersuite,
+            &bench_config.provider,
+            &creator_user,
+        )?;
+        key_packages.push(key_service.key_package(&device, now)?.clone());
+    }
+    let mut own_devices = Vec::new();
+    if !key_packages.is_empty() {
+        let (_, welcome) = add_members_checked(&mut group, bench_config, &key_packages, now)?;
+        let ratchet_tree: RatchetTreeIn = group.export_ratchet_tree().into();
+        for _ in &key_packages {
+            own_devices.push(join_from_welcome(
+                bench_config,
+                &welcome,
+                ratchet_tree.clone(),
+            )?);
+        }
+    }
+
+    let mut report = DeviceReport {
+        devices: group.members().count(),
+        ..Default::default()
+    };
+    report.state_per_device = saved_state_len(&mut group)?;
+
+    // Every leaf is a client, the creator's own leaf being `CREATOR`
+    let mut delivery = DeliveryService::new();
+    let members: Vec<_> = group.members().collect();
+    let clients: Vec<ClientId> = members
+        .iter()
+        .map(|member| member.index.u32() as ClientId)
+        .collect();
+    delivery.register_group(group.group_id().clone(), &clients, group.epoch().as_u64());
+    let message = group.create_message(
+        &bench_config.provider,
+        &bench_config.self_signer,
+        &vec![0u8; config.message_size],
+    )?;
+    delivery.send(group.group_id(), CREATOR, &message)?;
+
+    let (own, others): (Vec<_>, Vec<_>) = members
+        .iter()
+        .filter(|member| member.index.u32() as ClientId != CREATOR)
+        .partition(|member| device_user(member.credential.identity()) == creator_user);
+    for member in &own {
+        let Some(device) = own_devices
+            .iter_mut()
+            .find(|device| device.own_leaf_index() == member.index)
+        else {
+            bail!("Device of the creator is not in the group");
+        };
+        let envelopes = delivery.fetch(member.index.u32() as ClientId);
+        report.self_sync_writes += envelopes.len();
+        for envelope in envelopes {
+            let Envelope::Mls { message, .. } = envelope else {
+                bail!("Unexpected envelope");
+            };
+            receive_message(device, &bench_config.provider, &message)?;
+        }
+    }
+    report.self_sync_bytes = delivery.stats().delivered_bytes;
+    for member in &others {
+        delivery.fetch(member.index.u32() as ClientId);
+    }
+    report.message = MessageCost::from_stats(delivery.stats());
+
+    let device = key_service.add_device(
+        &bench_config.ciphersuite,
+        &bench_config.provider,
+        CHANGING_USER,
+    )?;
+    let (commit, welcome) =
+        add_member_from_key_service(&mut group, bench_config, &key_service, &device, now)?;
+    report.add_device_uploaded = commit.tls_serialized_len() + welcome.tls_serialized_len();
+
+    let Some(leaf) = group
+        .members()
+        .find(|member| member.credential.identity() == device.as_slice())
+    else {
+        bail!("Device is not in the group");
+    };
+    let (commit, _, _) = group.remove_members(
+        &bench_config.provider,
+        &bench_config.self_signer,
+        &[leaf.index],
+    )?;
+    group.merge_pending_commit(&bench_config.provider)?;
+    key_service.remove_device(CHANGING_USER, &device)?;
+    report.remove_device_uploaded = commit.tls_serialized_len();
+
+    Ok(report)
+}
+
+/// Every device keeps a session with every other device, its own user's
+/// included, so the sender encrypts once per device.
+pub fn ratchet_devices(config: &DeviceConfig, optimized: bool) -> Result<DeviceReport> {
+    config.check()?;
+    // The sender's view, with the sessions to its own other devices first
+    let own_devices = config.devices_per_user - 1;
+    let mut group = RatchetGroup::with_generated_members(config.devices() - 1);
+    let mut report = DeviceReport {
+        devices: group.len() + 1,
+        state_per_device: group.state_len(),
+        ..Default::default()
+    };
+
+    // Session `i` leads to client `i + 1`
+    let recipients: Vec<ClientId> = (CREATOR + 1..=CREATOR + group.len()).collect();
+    let mut delivery = DeliveryService::new();
+    let message = vec![0u8; config.message_size];
+    if optimized {
+        let (ciphertext, nonce, keys) = group.encrypt_message_efficiently(&message);
+        delivery.send_payload(CREATOR, &ciphertext, nonce, &recipients);
+        delivery.send_ratchet(CREATOR, recipients.iter().copied().zip(keys).collect());
+    } else {
+        let messages = group.encrypt_message(&message);
+        delivery.send_ratchet(CREATOR, recipients.iter().copied().zip(messages).collect());
+    }
+
+    for (session, &client) in recipients[..own_devices].iter().enumerate() {
+        let envelopes = delivery.fetch(client);
+        report.self_sync_writes += envelopes.len();
+        let mut payload = None;
+        for envelope in envelopes {
+            match envelope {
+                Envelope::Payload {
+                    ciphertext, nonce, ..
+                } => payload = Some((ciphertext, nonce)),
+                Envelope::Ratchet { message, .. } => {
+                    let plaintext = group.decrypt_at_member(session, &message)?;
+                    if optimized {
+                        let Some((ciphertext, nonce)) = payload.take() else {
+                            bail!("Key envelope without a payload");
+                        };
+                        let key: [u8; 32] = plaintext.as_slice().try_into()?;
+                        decrypt(&key, &ciphertext, &[], &nonce);
+                    }
+                }
+                _ => bail!("Unexpected envelope"),
+            }
+        }
+    }
+    report.self_sync_bytes = delivery.stats().delivered_bytes;
+    for &client in &recipients[own_devices..] {
+        delivery.fetch(client);
+    }
+    report.message = MessageCost::from_stats(delivery.stats());
+
+    // Sessions come from prekeys fetched out of band, the new device sets one
+    // up with every device in the group, the sender included. The group then
+    // learns of it from an instruction sent over every session.
+    report.add_device_sessions = group.len() + 1;
+    group.add_member();
+    let new_device = group.len() - 1;
+    let instructions = group.encrypt_message(&[0u8; INSTRUCTION_LEN]);
+    group.decrypt_at_member(new_device, &instructions[new_device])?;
+    report.add_device_uploaded = instructions.iter().map(message_len).sum();
+
+    // The rest of the group rekeys so the removed device cannot read on
+    group.remove_member_at(new_device);
+    let rekey = group.rekey(&[0u8; INSTRUCTION_LEN])?;
+    report.remove_device_uploaded = rekey.iter().map(message_len).sum();
+
+    Ok(report)
+}
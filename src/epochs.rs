@@ -0,0 +1,115 @@
+use anyhow::{bail, Result};
+use openmls::prelude::*;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+use crate::delivery::{ClientId, DeliveryService, Envelope};
+use crate::key_service::KeyService;
+use crate::mls::{
+    create_bare_group_with_joined_members, receive_message, saved_state_len, BenchConfig, CREATOR,
+};
+
+// The member whose messages arrive late
+const SENDER: ClientId = 1;
+
+#[derive(Debug)]
+pub struct MessageOutcome {
+    /// How many commits the receiver merged after the message was sent
+    pub epochs_behind: u64,
+    pub decrypted: bool,
+}
+
+#[derive(Debug)]
+pub struct LateDeliveryReport {
+    pub outcomes: Vec<MessageOutcome>,
+    /// Persisted receiver state just before the late messages arrive
+    pub receiver_state_len: usize,
+}
+
+impl LateDeliveryReport {
+    pub fn decrypted(&self) -> usize { self.outcomes.iter().filter(|o| o.decrypted).count() }
+
+    pub fn lost(&self) -> usize { self.outcomes.len() - self.decrypted() }
+}
+
+/// A member sends `messages_per_epoch` application messages in each of
+/// `commits + 1` epochs, while the group creator keeps committing updates.
+/// The messages wait in the creator's mailbox until after all commits and
+/// are then processed in an order shuffled by `seed`.
+pub fn simulate_late_delivery(
+    bench_config: &BenchConfig,
+    commits: usize,
+    messages_per_epoch: usize,
+    seed: u64,
+) -> Result<LateDeliveryReport> {
+    let mut key_service = KeyService::new();
+    key_service.generate(&bench_config.ciphersuite, &bench_config.provider, 1)?;
+    let (mut receiver, mut member_states) =
+        create_bare_group_with_joined_members(bench_config, &key_service, 1);
+    let mut sender = member_states.remove(0);
+
+    let group_id = receiver.group_id().clone();
+    let mut delivery = DeliveryService::new();
+    delivery.register_group(
+        group_id.clone(),
+        &[CREATOR, SENDER],
+        receiver.epoch().as_u64(),
+    );
+
+    for epoch in 0..=commits {
+        for _ in 0..messages_per_epoch {
+            let message = sender.group.create_message(
+                &bench_config.provider,
+                &sender.signer,
+                b"late message",
+            )?;
+            delivery.send(&group_id, SENDER, &message)?;
+        }
+
+        if epoch < commits {
+            let epoch = receiver.epoch().as_u64();
+            let (commit, _, _) =
+                receiver.self_update(&bench_config.provider, &bench_config.self_signer)?;
+            receiver.merge_pending_commit(&bench_config.provider)?;
+            delivery.submit_commit(&group_id, CREATOR, epoch, &commit)?;
+            for envelope in delivery.fetch(SENDER) {
+                let Envelope::Mls { message, .. } = envelope else {
+                    bail!("Not an MLS message");
+                };
+                receive_message(&mut sender.group, &bench_config.provider, &message)?;
+            }
+        }
+    }
+
+    // The receiver's mailbox holds the messages in sending order, which the
+    // network shuffles on the way
+    let mut in_flight: Vec<_> = delivery
+        .fetch(CREATOR)
+        .into_iter()
+        .enumerate()
+        .map(|(i, envelope)| (i / messages_per_epoch.max(1), envelope))
+        .collect();
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    for i in (1..in_flight.len()).rev() {
+        in_flight.swap(i, rng.next_u64() as usize % (i + 1));
+    }
+
+    let receiver_state_len = saved_state_len(&mut receiver)?;
+    let mut outcomes = Vec::with_capacity(in_flight.len());
+    for (epoch, envelope) in in_flight {
+        let Envelope::Mls { message, .. } = envelope else {
+            bail!("Not an MLS message");
+        };
+        outcomes.push(MessageOutcome {
+            epochs_behind: (commits - epoch) as u64,
+            decrypted: receive_message(&mut receiver, &bench_config.provider, &message).is_ok(),
+        });
+    }
+
+    Ok(LateDeliveryReport {
+        outcomes,
+        receiver_state_len,
+    })
+}
@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+use crate::ratchet::{RatchetGroup, SKIPPED_KEY_LEN};
+
+/// How the network mistreats pairwise ratchet messages.
+#[derive(Clone, Debug, Default)]
+pub struct FaultConfig {
+    /// Messages may be delayed behind up to this many later messages
+    pub reorder_window: usize,
+    pub drop_rate: f64,
+    pub duplicate_rate: f64,
+    pub seed: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct FaultReport {
+    pub sent: usize,
+    pub dropped: usize,
+    pub decrypted: usize,
+    pub rejected_duplicates: usize,
+    /// Messages refused because their gap was too long or their key was
+    /// already evicted from the skipped-key cache
+    pub rejected_by_limit: usize,
+    pub max_skipped_keys: usize,
+    /// Estimated from [`SKIPPED_KEY_LEN`]
+    pub max_cache_bytes: usize,
+}
+
+fn chance(rng: &mut ChaCha20Rng, probability: f64) -> bool {
+    (rng.next_u64() as f64 / u64::MAX as f64) < probability
+}
+
+/// Every member of a fresh ratchet group sends `messages` messages, which
+/// reach the local side dropped, duplicated and reordered according to
+/// `faults`.
+pub fn simulate_faulty_delivery(
+    members: usize,
+    messages: usize,
+    faults: &FaultConfig,
+) -> FaultReport {
+    let mut rng = ChaCha20Rng::seed_from_u64(faults.seed);
+    let mut group = RatchetGroup::with_generated_members(members);
+    let mut report = FaultReport::default();
+
+    for member in 0..members {
+        // (delivery slot, sequence number, message)
+        let mut in_flight = Vec::new();
+        for sequence in 0..messages {
+            let message = group.encrypt_from_member(member, b"faulty message");
+            report.sent += 1;
+            if chance(&mut rng, faults.drop_rate) {
+                report.dropped += 1;
+                continue;
+            }
+            let delay = rng.next_u64() as usize % (faults.reorder_window + 1);
+            if chance(&mut rng, faults.duplicate_rate) {
+                let duplicate_delay = rng.next_u64() as usize % (faults.reorder_window + 1);
+                in_flight.push((sequence + duplicate_delay, sequence, message.clone()));
+            }
+            in_flight.push((sequence + delay, sequence, message));
+        }
+        // Stable, so equal slots keep their sending order
+        in_flight.sort_by_key(|(slot, _, _)| *slot);
+
+        let mut delivered = HashSet::new();
+        for (_, sequence, (header, ciphertext, nonce)) in in_flight {
+            match group.try_decrypt_message(member, &header, &ciphertext, &nonce) {
+                Ok(_) => {
+                    delivered.insert(sequence);
+                    report.decrypted += 1;
+                }
+                Err(_) if delivered.contains(&sequence) => report.rejected_duplicates += 1,
+                Err(_) => report.rejected_by_limit += 1,
+            }
+            let skipped = group.skipped_keys(member);
+            report.max_skipped_keys = report.max_skipped_keys.max(skipped);
+            report.max_cache_bytes = report.max_cache_bytes.max(skipped * SKIPPED_KEY_LEN);
+        }
+    }
+
+    report
+}
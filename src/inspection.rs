@@ -0,0 +1,81 @@
+use anyhow::{bail, Result};
+use openmls::prelude::*;
+use openmls_rust_crypto::OpenMlsRustCrypto;
+
+use crate::mls::protocol_message;
+
+#[derive(Debug)]
+pub enum Inspection {
+    /// Encrypted handshake; only the epoch and content type are visible
+    Opaque {
+        epoch: GroupEpoch,
+        content_type: ContentType,
+    },
+    /// Public commit, verified and tracked by the server
+    Commit {
+        sender: Sender,
+        adds: usize,
+        removes: usize,
+        updates: usize,
+    },
+    /// Public proposal, verified by the server
+    Proposal { sender: Sender },
+}
+
+/// Delivery-service view of a group. The server follows the group from
+/// public handshake messages without holding any group secrets.
+pub struct Inspector {
+    group: PublicGroup,
+}
+
+impl Inspector {
+    pub fn new(
+        provider: &OpenMlsRustCrypto,
+        group_info_out: &MlsMessageOut,
+        ratchet_tree_in: RatchetTreeIn,
+    ) -> Result<Self> {
+        let group_info_in =
+            MlsMessageIn::tls_deserialize_exact(group_info_out.tls_serialize_detached()?)?;
+        let MlsMessageInBody::GroupInfo(group_info) = group_info_in.extract() else {
+            bail!("Not a GroupInfo message");
+        };
+
+        let (group, _) = PublicGroup::from_external(
+            provider,
+            ratchet_tree_in,
+            group_info,
+            ProposalStore::new(),
+        )?;
+        Ok(Self { group })
+    }
+
+    pub fn inspect(
+        &mut self,
+        provider: &OpenMlsRustCrypto,
+        message: &MlsMessageOut,
+    ) -> Result<Inspection> {
+        let message = protocol_message(message)?;
+        if let ProtocolMessage::PrivateMessage(_) = message {
+            return Ok(Inspection::Opaque {
+                epoch: message.epoch(),
+                content_type: message.content_type(),
+            });
+        }
+
+        let processed = self.group.process_message(provider, message)?;
+        let sender = processed.sender().clone();
+        match processed.into_content() {
+            ProcessedMessageContent::StagedCommitMessage(staged_commit) => {
+                let inspection = Inspection::Commit {
+                    sender,
+                    adds: staged_commit.add_proposals().count(),
+                    removes: staged_commit.remove_proposals().count(),
+                    updates: staged_commit.update_proposals().count(),
+                };
+                self.group.merge_commit(*staged_commit);
+                Ok(inspection)
+            }
+            _ => Ok(Inspection::Proposal { sender }),
+        }
+    }
+}
@@ -1,55 +1,447 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use openmls::prelude::*;
 use openmls_basic_credential::SignatureKeyPair;
+use rayon::prelude::*;
 
-use crate::credential::{create_keypackage, make_credential};
+use crate::auth::{AuthError, AuthenticationService};
+use crate::credential::{
+    create_keypackage_with_lifetime, make_credential, make_seeded_credential, make_x509_credential,
+};
+use crate::provider::{member_seeds, SeededProvider};
+use crate::x509::TestCa;
 
 pub struct MemberData {
-    pub key_package: KeyPackage,
+    /// Published key package, `None` once collected after it expired
+    pub key_package: Option<KeyPackage>,
     pub credential: CredentialWithKey,
     pub signature_pair: SignatureKeyPair,
 }
 
+// Same as the openmls default of 12 weeks
+pub const DEFAULT_LIFETIME: u64 = 60 * 60 * 24 * 7 * 12;
+
+// Separates the user from the device number in a device identity
+const DEVICE_SEPARATOR: u8 = b'#';
+
+/// Credential identity of device `number` of `user`, e.g. `Member 1#2`.
+pub fn device_identity(user: &[u8], number: usize) -> Vec<u8> {
+    [user, &[DEVICE_SEPARATOR], number.to_string().as_bytes()].concat()
+}
+
+/// User a credential identity belongs to. Identities that are not those of a
+/// device, such as the group creator's, are users of their own.
+pub fn device_user(identity: &[u8]) -> &[u8] {
+    identity
+        .iter()
+        .rposition(|&byte| byte == DEVICE_SEPARATOR)
+        .map_or(identity, |separator| &identity[..separator])
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Clock is before the Unix epoch")
+        .as_secs()
+}
+
+/// Unix times in seconds from and until which `key_package` is valid, taken
+/// from its lifetime extension.
+pub fn key_package_validity(key_package: &KeyPackage) -> Result<(u64, u64)> {
+    // Encoded as the two big-endian timestamps
+    let encoded = key_package.life_time().tls_serialize_detached()?;
+    let Ok(timestamps) = <[u8; 16]>::try_from(encoded.as_slice()) else {
+        bail!("Unexpected lifetime encoding");
+    };
+    let (not_before, not_after) = timestamps.split_at(8);
+    Ok((
+        u64::from_be_bytes(not_before.try_into()?),
+        u64::from_be_bytes(not_after.try_into()?),
+    ))
+}
+
+/// Refuses `key_package` unless its lifetime covers `now`.
+pub fn check_key_package_lifetime(key_package: &KeyPackage, now: u64) -> Result<()> {
+    let (not_before, not_after) = key_package_validity(key_package)?;
+    if now < not_before {
+        bail!("Key package is not valid yet");
+    }
+    if now > not_after {
+        bail!("Key package has expired");
+    }
+    Ok(())
+}
+
+// Whether `key_package` is missing or no longer valid `margin` seconds after
+// `now`
+fn needs_rotation(key_package: Option<&KeyPackage>, now: u64, margin: u64) -> bool {
+    key_package.map_or(true, |key_package| {
+        key_package_validity(key_package).map_or(true, |(_, not_after)| not_after <= now + margin)
+    })
+}
+
+fn member_data(
+    ciphersuite: &Ciphersuite,
+    provider: &impl OpenMlsCryptoProvider,
+    credential: CredentialWithKey,
+    signer: SignatureKeyPair,
+    lifetime: u64,
+) -> Result<MemberData> {
+    let key_package = create_keypackage_with_lifetime(
+        ciphersuite.clone(),
+        provider,
+        credential.clone(),
+        &signer,
+        lifetime,
+    )?;
+
+    Ok(MemberData {
+        key_package: Some(key_package),
+        credential,
+        signature_pair: signer,
+    })
+}
+
 pub struct KeyService {
     members: HashMap<Vec<u8>, MemberData>,
+    // Lifetime in seconds of the key packages generated from now on
+    lifetime: u64,
+    // Signature key -> credential, for every identity the service vouches for
+    registry: HashMap<Vec<u8>, Credential>,
+    revoked: HashSet<Vec<u8>>,
+    // User identity -> identities of its devices, each with its own keys
+    devices: HashMap<Vec<u8>, Vec<Vec<u8>>>,
 }
 
 impl KeyService {
-    pub fn new() -> Self {
+    pub fn new() -> Self { Self::with_lifetime(DEFAULT_LIFETIME) }
+
+    pub fn with_lifetime(lifetime: u64) -> Self {
         KeyService {
             members: Default::default(),
+            lifetime,
+            registry: Default::default(),
+            revoked: Default::default(),
+            devices: Default::default(),
         }
     }
 
-    pub fn generate(
+    /// Vouches for an identity that has no key package here, such as the
+    /// group creator.
+    pub fn trust(&mut self, credential: &CredentialWithKey) {
+        self.registry.insert(
+            credential.signature_key.as_slice().to_vec(),
+            credential.credential.clone(),
+        );
+    }
+
+    /// Marks the signing key of `identity` as revoked.
+    pub fn revoke(&mut self, identity: &[u8]) -> Result<()> {
+        let Some(member) = self.members.get(identity) else {
+            bail!("Unknown identity");
+        };
+        self.revoked
+            .insert(member.credential.signature_key.as_slice().to_vec());
+        Ok(())
+    }
+
+    pub fn is_revoked(&self, signature_key: &[u8]) -> bool { self.revoked.contains(signature_key) }
+
+    fn insert(&mut self, identity: Vec<u8>, data: MemberData) {
+        self.trust(&data.credential);
+        self.members.insert(identity, data);
+    }
+
+    // Generates members "Member 1" to "Member {count}" with credentials
+    // from `make_credential`, which gets the identity of each
+    fn generate_with(
         &mut self,
         ciphersuite: &Ciphersuite,
         provider: &impl OpenMlsCryptoProvider,
         count: usize,
+        make_credential: impl Fn(String) -> Result<(CredentialWithKey, SignatureKeyPair)>,
     ) -> Result<()> {
         for i in 1..=count {
             let identity = format!("Member {}", i);
-            let (new_credential, new_signer) =
-                make_credential(ciphersuite, provider, identity.clone())?;
-            let key_package = create_keypackage(
-                ciphersuite.clone(),
+            let (new_credential, new_signer) = make_credential(identity.clone())?;
+            let data = member_data(
+                ciphersuite,
                 provider,
-                new_credential.clone(),
-                &new_signer,
+                new_credential,
+                new_signer,
+                self.lifetime,
             )?;
+            self.insert(identity.into(), data);
+        }
+
+        Ok(())
+    }
 
-            let data = MemberData {
-                key_package,
-                credential: new_credential,
-                signature_pair: new_signer,
-            };
-            self.members.insert(identity.into(), data);
+    pub fn generate(
+        &mut self,
+        ciphersuite: &Ciphersuite,
+        provider: &impl OpenMlsCryptoProvider,
+        count: usize,
+    ) -> Result<()> {
+        self.generate_with(ciphersuite, provider, count, |identity| {
+            make_credential(ciphersuite, provider, identity)
+        })
+    }
+
+    /// Generates members holding certificate chains issued by `ca`. See
+    /// [`make_x509_credential`] for how close these are to real X.509
+    /// credentials.
+    pub fn generate_x509(
+        &mut self,
+        ciphersuite: &Ciphersuite,
+        provider: &impl OpenMlsCryptoProvider,
+        ca: &TestCa,
+        count: usize,
+    ) -> Result<()> {
+        self.generate_with(ciphersuite, provider, count, |identity| {
+            make_x509_credential(ciphersuite, provider, ca, identity)
+        })
+    }
+
+    /// Generates `users` users with `devices_per_user` devices each.
+    pub fn generate_devices(
+        &mut self,
+        ciphersuite: &Ciphersuite,
+        provider: &impl OpenMlsCryptoProvider,
+        users: usize,
+        devices_per_user: usize,
+    ) -> Result<()> {
+        for i in 1..=users {
+            let user = format!("Member {}", i);
+            for _ in 0..devices_per_user {
+                self.add_device(ciphersuite, provider, user.as_bytes())?;
+            }
         }
 
         Ok(())
     }
 
+    /// Publishes a key package for a new device of `user`, returning the
+    /// identity of the device. The identity names the user, see
+    /// [`device_identity`].
+    pub fn add_device(
+        &mut self,
+        ciphersuite: &Ciphersuite,
+        provider: &impl OpenMlsCryptoProvider,
+        user: &[u8],
+    ) -> Result<Vec<u8>> {
+        let identity = (1..)
+            .map(|i| device_identity(user, i))
+            .find(|identity| !self.members.contains_key(identity))
+            .expect("Device numbers are unbounded");
+        let (new_credential, new_signer) = make_credential(
+            ciphersuite,
+            provider,
+            String::from_utf8_lossy(&identity).into(),
+        )?;
+        let data = member_data(
+            ciphersuite,
+            provider,
+            new_credential,
+            new_signer,
+            self.lifetime,
+        )?;
+        self.insert(identity.clone(), data);
+        self.devices
+            .entry(user.to_vec())
+            .or_default()
+            .push(identity.clone());
+
+        Ok(identity)
+    }
+
+    /// Forgets `device` of `user`, which can no longer authenticate.
+    pub fn remove_device(&mut self, user: &[u8], device: &[u8]) -> Result<()> {
+        let Some(devices) = self.devices.get_mut(user) else {
+            bail!("Unknown user");
+        };
+        let Some(index) = devices.iter().position(|d| d == device) else {
+            bail!("Unknown device");
+        };
+        devices.remove(index);
+        if let Some(member) = self.members.remove(device) {
+            self.registry
+                .remove(member.credential.signature_key.as_slice());
+        }
+
+        Ok(())
+    }
+
+    pub fn devices(&self, user: &[u8]) -> &[Vec<u8>] {
+        self.devices.get(user).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn member(&self, identity: &[u8]) -> Option<&MemberData> { self.members.get(identity) }
+
+    /// Same as [`Self::generate_parallel`], on the calling thread, producing
+    /// the same keys for the same `seed`.
+    pub fn generate_seeded(
+        &mut self,
+        ciphersuite: &Ciphersuite,
+        provider: &impl OpenMlsCryptoProvider,
+        count: usize,
+        seed: u64,
+    ) -> Result<()> {
+        for i in 1..=count {
+            let (identity, data) = Self::generate_seeded_member(
+                ciphersuite,
+                provider.key_store(),
+                seed,
+                i,
+                self.lifetime,
+            )?;
+            self.insert(identity, data);
+        }
+
+        Ok(())
+    }
+
+    /// Generates members across all cores. Every member derives its keys from
+    /// `seed` and its index, so the result does not depend on scheduling.
+    /// `progress` receives the number of finished members and the total.
+    ///
+    /// Seeds are Ed25519 signing keys, so this fails for ciphersuites using
+    /// any other signature scheme.
+    pub fn generate_parallel<P: OpenMlsCryptoProvider>(
+        &mut self,
+        ciphersuite: &Ciphersuite,
+        provider: &P,
+        count: usize,
+        seed: u64,
+        progress: impl Fn(usize, usize) + Sync,
+    ) -> Result<()> {
+        let done = AtomicUsize::new(0);
+        let lifetime = self.lifetime;
+        let generated = (1..=count)
+            .into_par_iter()
+            .map(|i| {
+                let member = Self::generate_seeded_member(
+                    ciphersuite,
+                    provider.key_store(),
+                    seed,
+                    i,
+                    lifetime,
+                );
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1, count);
+                member
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (identity, data) in generated {
+            self.insert(identity, data);
+        }
+
+        Ok(())
+    }
+
+    fn generate_seeded_member<K: OpenMlsKeyStore>(
+        ciphersuite: &Ciphersuite,
+        key_store: &K,
+        seed: u64,
+        index: usize,
+        lifetime: u64,
+    ) -> Result<(Vec<u8>, MemberData)> {
+        let identity = format!("Member {}", index);
+        let (signing_seed, provider_seed) = member_seeds(seed, index);
+        let provider = SeededProvider::new(provider_seed, key_store);
+
+        let (new_credential, new_signer) =
+            make_seeded_credential(ciphersuite, &provider, identity.clone(), signing_seed)?;
+        let data = member_data(ciphersuite, &provider, new_credential, new_signer, lifetime)?;
+        Ok((identity.into(), data))
+    }
+
+    /// Returns the key package of `identity`, refusing it unless its own
+    /// lifetime covers `now`.
+    pub fn key_package(&self, identity: &[u8], now: u64) -> Result<&KeyPackage> {
+        let Some(member) = self.members.get(identity) else {
+            bail!("Unknown identity");
+        };
+        let Some(key_package) = &member.key_package else {
+            bail!("No key package published");
+        };
+        check_key_package_lifetime(key_package, now)?;
+        Ok(key_package)
+    }
+
+    /// Drops key packages that have expired by `now`, returning how many.
+    /// The members keep their credentials and can publish new packages with
+    /// [`Self::rotate`].
+    pub fn collect_garbage(&mut self, now: u64) -> usize {
+        let mut collected = 0;
+        for member in self.members.values_mut() {
+            let expired = member.key_package.as_ref().is_some_and(|key_package| {
+                key_package_validity(key_package).map_or(true, |(_, not_after)| not_after < now)
+            });
+            if expired {
+                member.key_package = None;
+                collected += 1;
+            }
+        }
+        collected
+    }
+
+    /// Issues fresh key packages for members whose packages expire within
+    /// `margin` seconds of `now` or were collected, returning how many were
+    /// refreshed.
+    pub fn rotate(
+        &mut self,
+        ciphersuite: &Ciphersuite,
+        provider: &impl OpenMlsCryptoProvider,
+        now: u64,
+        margin: u64,
+    ) -> Result<usize> {
+        let mut rotated = 0;
+        for member in self.members.values_mut() {
+            if !needs_rotation(member.key_package.as_ref(), now, margin) {
+                continue;
+            }
+            member.key_package = Some(create_keypackage_with_lifetime(
+                ciphersuite.clone(),
+                provider,
+                member.credential.clone(),
+                &member.signature_pair,
+                self.lifetime,
+            )?);
+            rotated += 1;
+        }
+
+        Ok(rotated)
+    }
+
+    /// Key packages currently published by all members.
+    pub fn key_packages(&self) -> Vec<KeyPackage> {
+        self.members
+            .values()
+            .filter_map(|member| member.key_package.clone())
+            .collect()
+    }
+
     pub fn all_data(&self) -> Vec<&MemberData> { self.members.values().collect() }
+
+    pub fn member_by_signature_key(&self, signature_key: &[u8]) -> Option<&MemberData> {
+        self.members
+            .values()
+            .find(|member| member.credential.signature_key.as_slice() == signature_key)
+    }
+}
+
+impl AuthenticationService for KeyService {
+    fn authenticate(&self, credential: &Credential, signature_key: &[u8]) -> Result<()> {
+        if self.is_revoked(signature_key) {
+            bail!(AuthError::Revoked);
+        }
+        match self.registry.get(signature_key) {
+            Some(registered) if registered == credential => Ok(()),
+            Some(_) => bail!(AuthError::IdentityMismatch),
+            None => bail!(AuthError::UnknownSignatureKey),
+        }
+    }
 }
@@ -1,6 +1,29 @@
 use openmls::prelude::*;
 
+#[cfg(feature = "async-sim")]
+pub mod actors;
+pub mod attachment;
+pub mod auth;
+pub mod conflict;
+pub mod cost;
 pub mod credential;
+pub mod delivery;
+pub mod devices;
+pub mod epochs;
+pub mod faults;
+pub mod inspection;
 pub mod key_service;
 pub mod mls;
-pub mod ratchet;
\ No newline at end of file
+pub mod multigroup;
+pub mod network;
+pub mod offline;
+pub mod padding;
+pub mod proposals;
+pub mod provider;
+pub mod psk;
+pub mod ratchet;
+pub mod revocation;
+pub mod state;
+pub mod synthetic;
+pub mod workload;
+pub mod x509;
@@ -1,39 +1,23 @@
-use anyhow::{bail, Result};
-use credential::{create_keypackage, make_credential};
+use anyhow::Result;
 use openmls::prelude::*;
-use openmls_rust_crypto::OpenMlsRustCrypto;
 use openmls_test::{
+    credential::{create_keypackage, make_credential},
     key_service::KeyService,
     mls::{create_group_with_members, BenchConfig},
 };
 
-mod credential;
-mod key_service;
-
-fn join_from_welcome(
-    provider: &OpenMlsRustCrypto,
-    welcome_out: &MlsMessageOut,
-    group_config: &MlsGroupConfig,
-    ratchet_tree_in: RatchetTreeIn,
-) -> Result<MlsGroup> {
-    let ser_welcome = welcome_out.tls_serialize_detached()?;
-    let welcome_in = MlsMessageIn::tls_deserialize(&mut ser_welcome.as_slice())?;
-
-    if let MlsMessageInBody::Welcome(welcome) = welcome_in.extract() {
-        let group =
-            MlsGroup::new_from_welcome(provider, group_config, welcome, Some(ratchet_tree_in))?;
-        Ok(group)
-    } else {
-        bail!("Not a welcome message");
-    }
-}
-
 fn main() -> Result<()> {
     let config = BenchConfig::default();
     for count in [2, 100, 1024] {
         let mut key_service = KeyService::new();
         key_service
-            .generate(&config.ciphersuite, &config.provider, count)
+            .generate_parallel(
+                &config.ciphersuite,
+                &config.provider,
+                count,
+                0,
+                |done, total| eprint!("\rGenerated {}/{} members", done, total),
+            )
             .expect("Failed to populate KeyService");
 
         let mut mls_group = create_group_with_members(&config, &key_service);
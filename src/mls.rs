@@ -1,16 +1,32 @@
+use anyhow::{bail, Context, Result};
 use openmls::credentials::CredentialWithKey;
 use openmls::framing::{MlsMessageIn, MlsMessageInBody, ProcessedMessageContent, ProtocolMessage};
 use openmls::group::config::CryptoConfig;
 use openmls::group::StagedCommit;
 use openmls::prelude::{
-    Ciphersuite, KeyPackage, MlsGroup, MlsGroupConfig, TlsDeserializeTrait, TlsSerializeTrait,
+    Ciphersuite, KeyPackage, MlsGroup, MlsGroupConfig, MlsGroupConfigBuilder, MlsMessageOut,
+    SenderRatchetConfiguration, TlsDeserializeTrait, TlsSerializeTrait, WireFormatPolicy,
 };
 use openmls::treesync::RatchetTreeIn;
 use openmls_basic_credential::SignatureKeyPair;
 use openmls_rust_crypto::OpenMlsRustCrypto;
+use openmls_traits::OpenMlsCryptoProvider;
 
-use crate::credential::make_credential;
-use crate::key_service::KeyService;
+use crate::auth::{
+    check_key_package, check_members, check_sender, check_staged_commit, AllowAll,
+    AuthenticationService,
+};
+use crate::credential::{make_credential, make_x509_credential};
+use crate::delivery::{ClientId, DeliveryService, Envelope};
+use crate::key_service::{check_key_package_lifetime, KeyService};
+use crate::padding::PaddingPolicy;
+use crate::x509::TestCa;
+
+// Client id of the group creator when traffic goes through a delivery service
+pub const CREATOR: ClientId = 0;
+
+// Same as the openmls default
+const DEFAULT_MAXIMUM_FORWARD_DISTANCE: u32 = 1000;
 
 pub struct BenchConfig {
     pub provider: OpenMlsRustCrypto,
@@ -18,26 +34,123 @@ pub struct BenchConfig {
     pub group_config: MlsGroupConfig,
     pub self_credential: CredentialWithKey,
     pub self_signer: SignatureKeyPair,
+    pub padding: PaddingPolicy,
 }
 
 impl Default for BenchConfig {
     fn default() -> Self {
+        Self::with_self_credential(|ciphersuite, provider| {
+            make_credential(ciphersuite, provider, "Alice".into())
+        })
+    }
+}
+
+fn default_group_config_builder(ciphersuite: Ciphersuite) -> MlsGroupConfigBuilder {
+    MlsGroupConfig::builder()
+        .crypto_config(CryptoConfig::with_default_version(ciphersuite))
+        .use_ratchet_tree_extension(false)
+}
+
+impl BenchConfig {
+    // Default configuration, with the group creator's credential from
+    // `make_credential`
+    fn with_self_credential(
+        make_credential: impl FnOnce(
+            &Ciphersuite,
+            &OpenMlsRustCrypto,
+        ) -> Result<(CredentialWithKey, SignatureKeyPair)>,
+    ) -> Self {
         let ciphersuite = Ciphersuite::MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519;
         let provider = OpenMlsRustCrypto::default();
-        let group_config = MlsGroupConfig::builder()
-            .crypto_config(CryptoConfig::with_default_version(ciphersuite))
-            .use_ratchet_tree_extension(false)
-            .build();
-        let (self_credential, self_signer) =
-            make_credential(&ciphersuite, &provider, "Alice".into()).unwrap();
+        let group_config = default_group_config_builder(ciphersuite).build();
+        let (self_credential, self_signer) = make_credential(&ciphersuite, &provider).unwrap();
         BenchConfig {
             provider,
             ciphersuite,
             group_config,
             self_credential,
             self_signer,
+            padding: PaddingPolicy::None,
         }
     }
+
+    /// Default configuration, with `configure` applied on top of the default
+    /// group configuration.
+    pub fn with_group_config(
+        configure: impl FnOnce(MlsGroupConfigBuilder) -> MlsGroupConfigBuilder,
+    ) -> Self {
+        let config = Self::default();
+        let group_config = configure(default_group_config_builder(config.ciphersuite)).build();
+        BenchConfig {
+            group_config,
+            ..config
+        }
+    }
+
+    /// Default configuration, padding application messages with `padding`.
+    /// openmls itself only pads to a block size, so other policies are
+    /// applied to the plaintext by [`create_padded_message`].
+    pub fn with_padding(padding: PaddingPolicy) -> Self {
+        let config = match padding {
+            PaddingPolicy::Block(block) => {
+                Self::with_group_config(|builder| builder.padding_size(block))
+            }
+            _ => Self::default(),
+        };
+        BenchConfig { padding, ..config }
+    }
+
+    /// Default configuration, sending handshake messages according to
+    /// `policy`.
+    pub fn with_wire_format_policy(policy: WireFormatPolicy) -> Self {
+        Self::with_group_config(|builder| builder.wire_format_policy(policy))
+    }
+
+    /// Default configuration, keeping secrets of `max_past_epochs` previous
+    /// epochs and accepting messages up to `out_of_order_tolerance`
+    /// generations behind the newest one received.
+    pub fn with_epoch_tolerance(max_past_epochs: usize, out_of_order_tolerance: u32) -> Self {
+        Self::with_group_config(|builder| {
+            builder
+                .max_past_epochs(max_past_epochs)
+                .sender_ratchet_configuration(SenderRatchetConfiguration::new(
+                    out_of_order_tolerance,
+                    DEFAULT_MAXIMUM_FORWARD_DISTANCE,
+                ))
+        })
+    }
+
+    /// Default configuration, with the group creator holding a certificate
+    /// chain issued by `ca`.
+    pub fn with_x509(ca: &TestCa) -> Self {
+        Self::with_self_credential(|ciphersuite, provider| {
+            make_x509_credential(ciphersuite, provider, ca, "Alice".into())
+        })
+    }
+}
+
+pub fn create_padded_message(
+    group: &mut MlsGroup,
+    bench_config: &BenchConfig,
+    msg: &[u8],
+) -> Result<MlsMessageOut> {
+    let plaintext = match bench_config.padding {
+        PaddingPolicy::Block(_) | PaddingPolicy::None => msg.to_vec(),
+        padding => padding.pad(msg),
+    };
+    Ok(group.create_message(
+        &bench_config.provider,
+        &bench_config.self_signer,
+        &plaintext,
+    )?)
+}
+
+/// Strips the padding [`create_padded_message`] added to the plaintext.
+pub fn strip_padding<'a>(bench_config: &BenchConfig, received: &'a [u8]) -> Result<&'a [u8]> {
+    match bench_config.padding {
+        PaddingPolicy::Block(_) | PaddingPolicy::None => Ok(received),
+        padding => padding.unpad(received),
+    }
 }
 
 pub fn create_group(bench_config: &BenchConfig) -> MlsGroup {
@@ -54,9 +167,8 @@ pub fn create_bare_group_with_members(
     bench_config: &BenchConfig,
     key_service: &KeyService,
 ) -> MlsGroup {
-    let members = key_service.all_data();
     let mut local_group = create_group(bench_config);
-    let key_packages: Vec<_> = members.into_iter().map(|m| m.key_package.clone()).collect();
+    let key_packages = key_service.key_packages();
 
     let _ = local_group
         .add_members(
@@ -72,75 +184,367 @@ pub fn create_bare_group_with_members(
     local_group
 }
 
+/// Adds the members of `key_packages` in one commit, refusing it if any
+/// package's own lifetime doesn't cover `now`.
+pub fn add_members_checked(
+    group: &mut MlsGroup,
+    bench_config: &BenchConfig,
+    key_packages: &[KeyPackage],
+    now: u64,
+) -> Result<(MlsMessageOut, MlsMessageOut)> {
+    for key_package in key_packages {
+        check_key_package_lifetime(key_package, now)?;
+    }
+    let (commit, welcome, _) = group.add_members(
+        &bench_config.provider,
+        &bench_config.self_signer,
+        key_packages,
+    )?;
+    group.merge_pending_commit(&bench_config.provider)?;
+    Ok((commit, welcome))
+}
+
+/// Adds `identity` using the key package published in `key_service`, which
+/// is refused once expired by `now`.
+pub fn add_member_from_key_service(
+    group: &mut MlsGroup,
+    bench_config: &BenchConfig,
+    key_service: &KeyService,
+    identity: &[u8],
+    now: u64,
+) -> Result<(MlsMessageOut, MlsMessageOut)> {
+    let key_package = key_service.key_package(identity, now)?.clone();
+    add_members_checked(group, bench_config, &[key_package], now)
+}
+
+/// A member's own view of the group, for scenarios where every participant
+/// processes the traffic.
+pub struct MemberState {
+    pub group: MlsGroup,
+    pub signer: SignatureKeyPair,
+}
+
+/// Like [`create_bare_group_with_members`], also joining every member from
+/// the Welcome.
+pub fn create_bare_group_with_member_states(
+    bench_config: &BenchConfig,
+    key_service: &KeyService,
+) -> (MlsGroup, Vec<MemberState>) {
+    create_bare_group_with_joined_members(bench_config, key_service, usize::MAX)
+}
+
+/// Like [`create_bare_group_with_member_states`], only joining the first
+/// `joined` members, for scenarios that need a few observers in a big group.
+pub fn create_bare_group_with_joined_members(
+    bench_config: &BenchConfig,
+    key_service: &KeyService,
+    joined: usize,
+) -> (MlsGroup, Vec<MemberState>) {
+    let mut local_group = create_group(bench_config);
+    let key_packages = key_service.key_packages();
+
+    let (_, welcome_out, _) = local_group
+        .add_members(
+            &bench_config.provider,
+            &bench_config.self_signer,
+            &key_packages,
+        )
+        .expect("Failed to add members");
+
+    local_group
+        .merge_pending_commit(&bench_config.provider)
+        .expect("Failed to merge pending commits");
+
+    let joiners: Vec<ClientId> = (CREATOR + 1..=CREATOR + joined.min(key_packages.len())).collect();
+    let mut delivery = DeliveryService::new();
+    delivery.send_welcome(
+        &welcome_out,
+        local_group.export_ratchet_tree().into(),
+        &joiners,
+    );
+    let member_states = joiners
+        .into_iter()
+        .map(|client| {
+            let Some(Envelope::Welcome {
+                welcome,
+                ratchet_tree,
+            }) = delivery.fetch(client).into_iter().next()
+            else {
+                panic!("No Welcome delivered");
+            };
+            // All key packages share one key store, so each join consumes
+            // whichever one openmls finds first; look up the signer afterwards
+            let group = join_from_welcome(bench_config, &welcome, ratchet_tree)
+                .expect("Group from welcome");
+            let own_key = own_signature_key(&group);
+            let signer = key_service
+                .member_by_signature_key(&own_key)
+                .expect("Joined with an unknown key package")
+                .signature_pair
+                .clone();
+            MemberState { group, signer }
+        })
+        .collect();
+
+    (local_group, member_states)
+}
+
+pub fn join_from_welcome(
+    bench_config: &BenchConfig,
+    welcome_out: &MlsMessageOut,
+    ratchet_tree_in: RatchetTreeIn,
+) -> Result<MlsGroup> {
+    join_from_welcome_with_provider(
+        &bench_config.provider,
+        &bench_config.group_config,
+        welcome_out,
+        ratchet_tree_in,
+    )
+}
+
+/// Like [`join_from_welcome`], for a joiner with a provider of its own.
+pub fn join_from_welcome_with_provider(
+    provider: &impl OpenMlsCryptoProvider,
+    group_config: &MlsGroupConfig,
+    welcome_out: &MlsMessageOut,
+    ratchet_tree_in: RatchetTreeIn,
+) -> Result<MlsGroup> {
+    let welcome_in = MlsMessageIn::tls_deserialize_exact(welcome_out.tls_serialize_detached()?)?;
+
+    let MlsMessageInBody::Welcome(welcome) = welcome_in.extract() else {
+        bail!("Not a welcome message");
+    };
+    Ok(MlsGroup::new_from_welcome(
+        provider,
+        group_config,
+        welcome,
+        Some(ratchet_tree_in),
+    )?)
+}
+
+/// Joins the group described by an exported GroupInfo through an external
+/// commit, which the existing members then have to process.
+pub fn join_by_external_commit(
+    bench_config: &BenchConfig,
+    group_info_out: &MlsMessageOut,
+    ratchet_tree_in: RatchetTreeIn,
+    credential: CredentialWithKey,
+    signer: &SignatureKeyPair,
+) -> Result<(MlsGroup, MlsMessageOut)> {
+    let group_info_in =
+        MlsMessageIn::tls_deserialize_exact(group_info_out.tls_serialize_detached()?)?;
+
+    let MlsMessageInBody::GroupInfo(group_info) = group_info_in.extract() else {
+        bail!("Not a GroupInfo message");
+    };
+    let (mut group, commit, _) = MlsGroup::join_by_external_commit(
+        &bench_config.provider,
+        signer,
+        Some(ratchet_tree_in),
+        group_info,
+        &bench_config.group_config,
+        &[],
+        credential,
+    )?;
+    group.merge_pending_commit(&bench_config.provider)?;
+    Ok((group, commit))
+}
+
+/// Size of the group state as persisted by openmls.
+pub fn saved_state_len(group: &mut MlsGroup) -> Result<usize> {
+    let mut saved = Vec::new();
+    group.save(&mut saved)?;
+    Ok(saved.len())
+}
+
+pub fn own_signature_key(group: &MlsGroup) -> Vec<u8> {
+    let own_index = group.own_leaf_index();
+    group
+        .members()
+        .find(|member| member.index == own_index)
+        .map(|member| member.signature_key)
+        .expect("Own leaf is missing")
+}
+
+pub fn protocol_message(message: &MlsMessageOut) -> Result<ProtocolMessage> {
+    let message_in = MlsMessageIn::tls_deserialize_exact(message.tls_serialize_detached()?)?;
+    match message_in.extract() {
+        MlsMessageInBody::PrivateMessage(message) => Ok(message.into()),
+        MlsMessageInBody::PublicMessage(message) => Ok(message.into()),
+        _ => bail!("Not a protocol message"),
+    }
+}
+
+pub enum Received {
+    Application(Vec<u8>),
+    Proposal,
+    Commit,
+}
+
+/// Processes a message as its recipient would: application messages are
+/// returned, proposals stored and commits merged.
+pub fn receive_message(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    message: &MlsMessageOut,
+) -> Result<Received> {
+    receive_authenticated_message(group, provider, message, &AllowAll)
+}
+
+pub fn receive_authenticated_message(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    message: &MlsMessageOut,
+    auth: &impl AuthenticationService,
+) -> Result<Received> {
+    let processed = group.process_message(provider, protocol_message(message)?)?;
+    check_sender(auth, group, processed.sender())?;
+
+    match processed.into_content() {
+        ProcessedMessageContent::ApplicationMessage(application_message) => {
+            Ok(Received::Application(application_message.into_bytes()))
+        }
+        ProcessedMessageContent::ProposalMessage(proposal)
+        | ProcessedMessageContent::ExternalJoinProposalMessage(proposal) => {
+            group.store_pending_proposal(*proposal);
+            Ok(Received::Proposal)
+        }
+        ProcessedMessageContent::StagedCommitMessage(staged_commit) => {
+            check_staged_commit(auth, &staged_commit)?;
+            group.merge_staged_commit(provider, *staged_commit)?;
+            Ok(Received::Commit)
+        }
+    }
+}
+
+// Merges a commit that has to arrive encrypted, as the updates mending the
+// tree do with the default wire format policy
+fn receive_private_commit(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    message: &MlsMessageOut,
+    auth: &impl AuthenticationService,
+) -> Result<()> {
+    let message_in = MlsMessageIn::tls_deserialize_exact(message.tls_serialize_detached()?)?;
+    let MlsMessageInBody::PrivateMessage(private_message) = message_in.extract() else {
+        bail!("Not a PrivateMessage");
+    };
+    let processed = group.process_message(provider, private_message)?;
+    check_sender(auth, group, processed.sender())?;
+
+    let ProcessedMessageContent::StagedCommitMessage(staged_commit) = processed.into_content()
+    else {
+        bail!("Not a commit");
+    };
+    check_staged_commit(auth, &staged_commit)?;
+    group.merge_staged_commit(provider, *staged_commit)?;
+    Ok(())
+}
+
 pub fn create_group_with_members(bench_config: &BenchConfig, key_service: &KeyService) -> MlsGroup {
-    let members = key_service.all_data();
+    create_authenticated_group_with_members(bench_config, key_service, &AllowAll)
+        .expect("Failed to create group")
+}
+
+/// Like [`create_group_with_members`], also returning the view of the member
+/// that joined last, which is up to date once the tree is mended.
+pub fn create_group_with_last_member(
+    bench_config: &BenchConfig,
+    key_service: &KeyService,
+) -> (MlsGroup, MemberState) {
+    let (group, last) =
+        mend_group(bench_config, key_service, &AllowAll).expect("Failed to create group");
+    (group, last.expect("Group has no members"))
+}
+
+/// Like [`create_group_with_members`], running `auth` on every added key
+/// package, every Welcome join and every processed commit.
+pub fn create_authenticated_group_with_members(
+    bench_config: &BenchConfig,
+    key_service: &KeyService,
+    auth: &impl AuthenticationService,
+) -> Result<MlsGroup> {
+    let (group, _) = mend_group(bench_config, key_service, auth)?;
+    Ok(group)
+}
+
+fn mend_group(
+    bench_config: &BenchConfig,
+    key_service: &KeyService,
+    auth: &impl AuthenticationService,
+) -> Result<(MlsGroup, Option<MemberState>)> {
+    // Members whose key packages were collected can't be added
+    let members: Vec<_> = key_service
+        .all_data()
+        .into_iter()
+        .filter_map(|member| Some((member, member.key_package.as_ref()?)))
+        .collect();
     let mut local_group = create_group(bench_config);
 
+    let group_id = local_group.group_id().clone();
+    let mut delivery = DeliveryService::new();
+    delivery.register_group(group_id.clone(), &[CREATOR], local_group.epoch().as_u64());
+
+    let mut last = None;
     // Mend tree by updating each leaf
-    for (i, member) in members.iter().enumerate() {
-        let (message_out, welcome_out, _) = local_group
+    for (i, &(member, key_package)) in members.iter().enumerate() {
+        let client = i + 1;
+        check_key_package(auth, key_package)?;
+        let epoch = local_group.epoch().as_u64();
+        let (commit, welcome_out, _) = local_group
             .add_members(
                 &bench_config.provider,
                 &bench_config.self_signer,
-                &[member.key_package.clone()],
+                &[key_package.clone()],
             )
-            .expect("Failed to add members");
+            .context("Failed to add members")?;
+        delivery.submit_commit(&group_id, CREATOR, epoch, &commit)?;
 
         local_group
             .merge_pending_commit(&bench_config.provider)
-            .expect("Failed to merge pending commits");
+            .context("Failed to merge pending commits")?;
 
         let ratchet_tree_in: RatchetTreeIn = local_group.export_ratchet_tree().into();
+        delivery.send_welcome(&welcome_out, ratchet_tree_in, &[client]);
+        delivery.add_to_group(&group_id, client)?;
 
-        let welcome_in = MlsMessageIn::tls_deserialize(
-            &mut welcome_out
-                .tls_serialize_detached()
-                .expect("Deserialize out")
-                .as_slice(),
-        )
-        .expect("Deserialize in");
+        let Some(Envelope::Welcome {
+            welcome,
+            ratchet_tree,
+        }) = delivery.fetch(client).into_iter().next()
+        else {
+            bail!("No Welcome delivered");
+        };
+        let mut remote_group = join_from_welcome(bench_config, &welcome, ratchet_tree)
+            .context("Group from welcome")?;
+        check_members(auth, &remote_group)?;
 
-        if let MlsMessageInBody::Welcome(welcome) = welcome_in.extract() {
-            let mut remote_group = MlsGroup::new_from_welcome(
-                &bench_config.provider,
-                &bench_config.group_config,
-                welcome.clone(),
-                Some(ratchet_tree_in.clone()),
-            )
-            .expect("Group from welcome");
-            let (update_out, _, _) = remote_group
-                .self_update(&bench_config.provider, &member.signature_pair)
-                .expect("Failed to update remote leaf");
-
-            let update_in: MlsMessageInBody = MlsMessageIn::tls_deserialize_exact(
-                update_out
-                    .tls_serialize_detached()
-                    .expect("Serialize update"),
-            )
-            .expect("Deserialize update")
-            .extract();
-
-            if let MlsMessageInBody::PrivateMessage(update_message) = update_in {
-                let processed_update = local_group
-                    .process_message(&bench_config.provider, update_message)
-                    .expect("Process update");
-
-                if let ProcessedMessageContent::StagedCommitMessage(staged_commit) =
-                    processed_update.into_content()
-                {
-                    local_group.merge_staged_commit(&bench_config.provider, *staged_commit);
-                } else {
-                    panic!("Not a commit");
-                }
-            } else {
-                panic!("Not a PrivateMessage");
-            }
-        } else {
-            panic!("Not a welcome message");
+        let epoch = remote_group.epoch().as_u64();
+        let (update_out, _, _) = remote_group
+            .self_update(&bench_config.provider, &member.signature_pair)
+            .context("Failed to update remote leaf")?;
+        delivery.submit_commit(&group_id, client, epoch, &update_out)?;
+        remote_group
+            .merge_pending_commit(&bench_config.provider)
+            .context("Failed to merge remote update")?;
+        // Only the state of the last member is kept, and nothing is sent after
+        // its update, so no other joined state needs more deliveries
+        delivery.remove_from_group(&group_id, client)?;
+        last = Some(MemberState {
+            group: remote_group,
+            signer: member.signature_pair.clone(),
+        });
+
+        for envelope in delivery.fetch(CREATOR) {
+            let Envelope::Mls { message, .. } = envelope else {
+                bail!("Not an MLS message");
+            };
+            receive_private_commit(&mut local_group, &bench_config.provider, &message, auth)
+                .context("Process update")?;
         }
 
         eprint!("\rMember {} done", i);
     }
 
-    local_group
+    Ok((local_group, last))
 }
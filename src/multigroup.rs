@@ -0,0 +1,233 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use openmls::prelude::*;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+use crate::credential::{create_keypackage, make_credential};
+use crate::key_service::KeyService;
+use crate::mls::{
+    create_bare_group_with_members, join_from_welcome_with_provider, protocol_message, BenchConfig,
+};
+use crate::provider::{member_seeds, CountingKeyStore, SeededProvider};
+use crate::ratchet::RatchetGroup;
+
+#[derive(Clone, Debug)]
+pub struct MultiGroupConfig {
+    pub groups: usize,
+    /// Group sizes, each group draws one of them
+    pub sizes: Vec<usize>,
+    /// Other people the client knows, group members are drawn from them
+    pub contacts: usize,
+    /// Incoming messages used to measure dispatch, from random groups
+    pub messages: usize,
+    pub seed: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct MultiGroupReport {
+    /// Group memberships of other members, summed over all groups
+    pub memberships: usize,
+    /// Persisted state of all MLS groups of the client, its key store
+    /// included
+    pub mls_state: usize,
+    /// Part of [`Self::mls_state`] taken by the key store the groups share
+    pub mls_key_store: usize,
+    /// Time to load the key store and every group
+    pub mls_load_time: Duration,
+    /// Time from an incoming MLS message to its plaintext, per message
+    pub mls_dispatch_time: Duration,
+    /// Contacts sharing at least one group with the client
+    pub peers: usize,
+    /// Estimated ratchet state with one session per peer, shared by all
+    /// groups, see [`RatchetGroup::state_len`]
+    pub shared_ratchet_state: usize,
+    /// Estimated ratchet state if every group kept its own sessions
+    pub per_group_ratchet_state: usize,
+    pub ratchet_dispatch_time: Duration,
+}
+
+// Group sizes and the contacts in each group besides the client
+fn draw_groups(config: &MultiGroupConfig) -> Vec<Vec<usize>> {
+    let mut rng = ChaCha20Rng::seed_from_u64(config.seed);
+    let mut contacts: Vec<usize> = (0..config.contacts).collect();
+    (0..config.groups)
+        .map(|_| {
+            let size = config.sizes[rng.next_u64() as usize % config.sizes.len()];
+            let others = (size - 1).min(contacts.len());
+            // Partial shuffle, the first `others` contacts are the members
+            for i in 0..others {
+                let j = i + rng.next_u64() as usize % (contacts.len() - i);
+                contacts.swap(i, j);
+            }
+            contacts[..others].to_vec()
+        })
+        .collect()
+}
+
+/// One client in many groups, all sharing the client's own key store. MLS
+/// needs a group state per group, while pairwise ratchets only need one
+/// session per peer.
+pub fn simulate_many_groups(
+    bench_config: &BenchConfig,
+    config: &MultiGroupConfig,
+) -> Result<MultiGroupReport> {
+    let groups = draw_groups(config);
+    let mut report = MultiGroupReport {
+        memberships: groups.iter().map(Vec::len).sum(),
+        ..Default::default()
+    };
+
+    mls_groups(bench_config, config, &groups, &mut report)?;
+    ratchet_groups(config, &groups, &mut report)?;
+
+    Ok(report)
+}
+
+fn mls_groups(
+    bench_config: &BenchConfig,
+    config: &MultiGroupConfig,
+    groups: &[Vec<usize>],
+    report: &mut MultiGroupReport,
+) -> Result<()> {
+    // The client joins every group with one identity and one key store
+    let key_store = CountingKeyStore::default();
+    let (_, provider_seed) = member_seeds(config.seed, 0);
+    let provider = SeededProvider::new(provider_seed, &key_store);
+    let (credential, signer) =
+        make_credential(&bench_config.ciphersuite, &provider, "Client".into())?;
+
+    let mut client_groups = HashMap::new();
+    // The creator of each group sends the incoming messages, standing in for
+    // one of the contacts
+    let mut senders = Vec::new();
+    for members in groups {
+        let mut key_service = KeyService::new();
+        key_service.generate(
+            &bench_config.ciphersuite,
+            &bench_config.provider,
+            members.len().saturating_sub(1),
+        )?;
+        let mut creator = create_bare_group_with_members(bench_config, &key_service);
+        let key_package = create_keypackage(
+            bench_config.ciphersuite,
+            &provider,
+            credential.clone(),
+            &signer,
+        )?;
+        let (_, welcome, _) = creator.add_members(
+            &bench_config.provider,
+            &bench_config.self_signer,
+            &[key_package],
+        )?;
+        creator.merge_pending_commit(&bench_config.provider)?;
+        let group = join_from_welcome_with_provider(
+            &provider,
+            &bench_config.group_config,
+            &welcome,
+            creator.export_ratchet_tree().into(),
+        )?;
+        client_groups.insert(group.group_id().clone(), group);
+        senders.push(creator);
+    }
+
+    let mut saved = Vec::with_capacity(client_groups.len());
+    for group in client_groups.values_mut() {
+        let mut state = Vec::new();
+        group.save(&mut state)?;
+        report.mls_state += state.len();
+        saved.push(state);
+    }
+    let saved_key_store = key_store.save()?;
+    report.mls_key_store = saved_key_store.len();
+    report.mls_state += report.mls_key_store;
+    let start = Instant::now();
+    CountingKeyStore::load(&saved_key_store)?;
+    for state in &saved {
+        MlsGroup::load(state.as_slice()).context("Failed to load group")?;
+    }
+    report.mls_load_time = start.elapsed();
+
+    let mut rng = ChaCha20Rng::seed_from_u64(config.seed);
+    let mut incoming = Vec::with_capacity(config.messages);
+    for _ in 0..config.messages {
+        let sender = &mut senders[rng.next_u64() as usize % senders.len()];
+        incoming.push(sender.create_message(
+            &bench_config.provider,
+            &bench_config.self_signer,
+            b"dispatched message",
+        )?);
+    }
+
+    let start = Instant::now();
+    for message in &incoming {
+        let message = protocol_message(message)?;
+        let group = client_groups
+            .get_mut(message.group_id())
+            .context("Message for an unknown group")?;
+        group.process_message(&provider, message)?;
+    }
+    report.mls_dispatch_time = start.elapsed() / config.messages.max(1) as u32;
+
+    Ok(())
+}
+
+fn ratchet_groups(
+    config: &MultiGroupConfig,
+    groups: &[Vec<usize>],
+    report: &mut MultiGroupReport,
+) -> Result<()> {
+    let peers: HashSet<usize> = groups.iter().flatten().copied().collect();
+    let mut peers: Vec<usize> = peers.into_iter().collect();
+    peers.sort();
+    report.peers = peers.len();
+
+    let mut sessions = RatchetGroup::with_generated_members(peers.len());
+    report.shared_ratchet_state = sessions.state_len();
+    // Fresh sessions all have the same size
+    report.per_group_ratchet_state =
+        report.memberships * RatchetGroup::with_generated_members(1).state_len();
+
+    // Contact -> session, group -> members, as a client would keep them
+    let session_of: HashMap<usize, usize> = peers
+        .iter()
+        .enumerate()
+        .map(|(session, &contact)| (contact, session))
+        .collect();
+    let group_members: HashMap<u32, HashSet<usize>> = groups
+        .iter()
+        .enumerate()
+        .map(|(group, members)| (group as u32, members.iter().copied().collect()))
+        .collect();
+
+    let mut rng = ChaCha20Rng::seed_from_u64(config.seed);
+    let mut incoming = Vec::with_capacity(config.messages);
+    for _ in 0..config.messages {
+        let group = rng.next_u64() as usize % groups.len();
+        let Some(&contact) = groups[group].first() else {
+            continue;
+        };
+        // The group a message belongs to travels inside the ciphertext
+        let message =
+            sessions.encrypt_from_member(session_of[&contact], &(group as u32).to_be_bytes());
+        incoming.push((contact, message));
+    }
+
+    let start = Instant::now();
+    for (contact, (header, ciphertext, nonce)) in &incoming {
+        let plaintext =
+            sessions.try_decrypt_message(session_of[contact], header, ciphertext, nonce)?;
+        let group = u32::from_be_bytes(plaintext.as_slice().try_into()?);
+        group_members
+            .get(&group)
+            .filter(|members| members.contains(contact))
+            .context("Message for a group the sender is not in")?;
+    }
+    report.ratchet_dispatch_time = start.elapsed() / config.messages.max(1) as u32;
+
+    Ok(())
+}
@@ -0,0 +1,735 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use anyhow::{bail, Result};
+use double_ratchet_2::ratchet::Ratchet;
+use openmls::prelude::*;
+use openmls::treesync::RatchetTreeIn;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+use x25519_dalek::StaticSecret;
+
+use crate::delivery::{ClientId, DeliveryService, Envelope};
+use crate::key_service::KeyService;
+use crate::mls::{
+    create_bare_group_with_member_states, join_from_welcome, own_signature_key, receive_message,
+    BenchConfig, MemberState, Received, CREATOR,
+};
+use crate::ratchet::{catch_decrypt, RatchetGroup, RatchetMessage};
+
+/// One-way latency of a link in milliseconds.
+#[derive(Clone, Copy, Debug)]
+pub enum Latency {
+    Constant(u64),
+    /// Between a minimum and a maximum, both inclusive
+    Uniform(u64, u64),
+    /// Exponentially distributed around a mean
+    Exponential(u64),
+}
+
+/// Cuts `clients` off the delivery service from `start` until `end`.
+/// Messages from and to them wait on the other side of the partition.
+#[derive(Clone, Debug)]
+pub struct Partition {
+    pub clients: Vec<ClientId>,
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct NetworkConfig {
+    pub latency: Latency,
+    /// Chance that a transmission is lost and has to be repeated
+    pub loss_rate: f64,
+    pub retransmit_timeout: u64,
+    pub partitions: Vec<Partition>,
+    pub seed: u64,
+}
+
+impl NetworkConfig {
+    /// Refuses configurations the scheduler can't run: a certain loss would
+    /// retransmit forever and an inverted range has no latency to draw.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..1.0).contains(&self.loss_rate) {
+            bail!("Loss rate {} is not in [0, 1)", self.loss_rate);
+        }
+        if let Latency::Uniform(min, max) = self.latency {
+            if min > max {
+                bail!("Latency range {}..={} is empty", min, max);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            latency: Latency::Constant(50),
+            loss_rate: 0.0,
+            retransmit_timeout: 200,
+            partitions: Vec::new(),
+            seed: 0,
+        }
+    }
+}
+
+/// Direction of a link between a client and the delivery service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Link {
+    Up(ClientId),
+    Down(ClientId),
+}
+
+/// Deterministic discrete-event scheduler. Links deliver in order, like a
+/// TCP connection, and lost transmissions are repeated after a timeout.
+pub struct Network<T> {
+    config: NetworkConfig,
+    rng: ChaCha20Rng,
+    now: u64,
+    next_sequence: u64,
+    // (time, sequence) of pending events, the sequence breaks ties in
+    // scheduling order
+    queue: BinaryHeap<Reverse<(u64, u64)>>,
+    // Events with the link they travel over, timers have none
+    events: HashMap<u64, (Option<Link>, T)>,
+    last_arrival: HashMap<Link, u64>,
+    retransmissions: usize,
+}
+
+impl<T> Network<T> {
+    pub fn new(config: NetworkConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Network {
+            rng: ChaCha20Rng::seed_from_u64(config.seed),
+            config,
+            now: 0,
+            next_sequence: 0,
+            queue: BinaryHeap::new(),
+            events: HashMap::new(),
+            last_arrival: HashMap::new(),
+            retransmissions: 0,
+        })
+    }
+
+    pub fn now(&self) -> u64 { self.now }
+
+    pub fn retransmissions(&self) -> usize { self.retransmissions }
+
+    fn uniform(&mut self) -> f64 { self.rng.next_u64() as f64 / (u64::MAX as f64 + 1.0) }
+
+    fn sample_latency(&mut self) -> u64 {
+        match self.config.latency {
+            Latency::Constant(latency) => latency,
+            Latency::Uniform(min, max) => min + self.rng.next_u64() % (max - min + 1),
+            Latency::Exponential(mean) => (-(mean as f64) * (1.0 - self.uniform()).ln()) as u64,
+        }
+    }
+
+    fn partition_end(&self, client: ClientId, at: u64) -> Option<u64> {
+        self.config
+            .partitions
+            .iter()
+            .filter(|p| p.clients.contains(&client) && p.start <= at && at < p.end)
+            .map(|p| p.end)
+            .max()
+    }
+
+    fn push(&mut self, time: u64, link: Option<Link>, event: T) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.queue.push(Reverse((time.max(self.now), sequence)));
+        self.events.insert(sequence, (link, event));
+    }
+
+    /// Schedules `event` at an absolute time, such as a local timer.
+    pub fn schedule_at(&mut self, time: u64, event: T) { self.push(time, None, event); }
+
+    /// Sends `event` over `link`, returning its arrival time.
+    pub fn send(&mut self, link: Link, event: T) -> u64 {
+        let (Link::Up(client) | Link::Down(client)) = link;
+        let mut ready = self.now;
+        while let Some(end) = self.partition_end(client, ready) {
+            ready = end;
+        }
+        let mut arrival = ready + self.sample_latency();
+        while self.uniform() < self.config.loss_rate {
+            self.retransmissions += 1;
+            arrival += self.config.retransmit_timeout;
+        }
+        // Later transmissions never overtake earlier ones on the same link
+        let last = self.last_arrival.entry(link).or_default();
+        arrival = arrival.max(*last);
+        *last = arrival;
+
+        self.push(arrival, Some(link), event);
+        arrival
+    }
+
+    /// Advances the clock to the next event. Transmissions arriving while
+    /// their client is cut off wait until the partition ends.
+    pub fn next(&mut self) -> Option<T> {
+        loop {
+            let Reverse((time, sequence)) = self.queue.pop()?;
+            self.now = time;
+            let (link, event) = self.events.remove(&sequence)?;
+            let Some(link) = link else {
+                return Some(event);
+            };
+            let (Link::Up(client) | Link::Down(client)) = link;
+            let Some(mut end) = self.partition_end(client, time) else {
+                return Some(event);
+            };
+            while let Some(later) = self.partition_end(client, end) {
+                end = later;
+            }
+            // Keeping the sequence keeps the transmission ahead of the later
+            // ones on the link that arrive at the same time
+            let last = self.last_arrival.entry(link).or_default();
+            *last = (*last).max(end);
+            self.queue.push(Reverse((end, sequence)));
+            self.events.insert(sequence, (Some(link), event));
+        }
+    }
+}
+
+/// Traffic of a network simulation. The delivery service has no timer of
+/// its own, it is driven by the uploads reaching it.
+#[derive(Clone, Debug)]
+pub struct NetworkScenario {
+    /// Members besides the creator at the start
+    pub members: usize,
+    /// Members added one by one, `commit_interval` milliseconds apart
+    pub joiners: usize,
+    pub commit_interval: u64,
+    /// Every member sends an application message this often
+    pub message_interval: u64,
+    /// No new traffic is created after this time
+    pub duration: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct NetworkReport {
+    /// Milliseconds from each membership change until every member reached
+    /// the new state, `None` if some never did
+    pub convergence_times: Vec<Option<u64>>,
+    /// Application messages that reached a recipient, decryptable or not
+    pub received: usize,
+    /// Messages a recipient could not decrypt, because it was in a different
+    /// epoch than the sender
+    pub undecryptable: usize,
+    pub retransmissions: usize,
+}
+
+impl NetworkReport {
+    /// Share of received application messages that could not be decrypted.
+    pub fn undecryptable_fraction(&self) -> f64 {
+        if self.received == 0 {
+            return 0.0;
+        }
+        self.undecryptable as f64 / self.received as f64
+    }
+}
+
+enum Upload {
+    Commit {
+        epoch: u64,
+        commit: MlsMessageOut,
+        welcome: MlsMessageOut,
+        ratchet_tree: RatchetTreeIn,
+        joiner: ClientId,
+    },
+    Message(MlsMessageOut),
+}
+
+enum MlsEvent {
+    /// Timer of a member sending an application message
+    Send(ClientId),
+    /// Timer of the creator adding the next joiner
+    Add(usize),
+    Upload(ClientId, Upload),
+    /// The delivery service notifies a client of waiting messages
+    Download(ClientId),
+}
+
+struct Participant {
+    state: MemberState,
+    joined_epoch: u64,
+    // Epochs this participant reached and when
+    reached: Vec<(u64, u64)>,
+}
+
+fn notify<T>(
+    network: &mut Network<T>,
+    delivery: &DeliveryService,
+    notified: &mut HashSet<ClientId>,
+    clients: impl Iterator<Item = ClientId>,
+    event: impl Fn(ClientId) -> T,
+) {
+    for client in clients {
+        if delivery.pending(client) > 0 && notified.insert(client) {
+            network.send(Link::Down(client), event(client));
+        }
+    }
+}
+
+// Time from `changed_at` until every participant that joined no later than
+// `epoch` reached it
+fn convergence_time<'a>(
+    participants: impl Iterator<Item = &'a (u64, Vec<(u64, u64)>)>,
+    epoch: u64,
+    changed_at: u64,
+) -> Option<u64> {
+    let mut converged_at = changed_at;
+    for (joined_epoch, reached) in participants {
+        if *joined_epoch > epoch {
+            continue;
+        }
+        let (_, time) = reached.iter().find(|(reached, _)| *reached >= epoch)?;
+        converged_at = converged_at.max(*time);
+    }
+    Some(converged_at - changed_at)
+}
+
+fn schedule_sends<T>(
+    network: &mut Network<T>,
+    clients: usize,
+    scenario: &NetworkScenario,
+    event: impl Fn(ClientId) -> T,
+) {
+    for client in 0..clients {
+        // Offset members a little so they don't all send at once
+        let mut time = client as u64 % scenario.message_interval.max(1);
+        while time < scenario.duration {
+            network.schedule_at(time, event(client));
+            time += scenario.message_interval.max(1);
+        }
+    }
+}
+
+/// Runs an MLS group over the simulated network. The creator adds the
+/// joiners while all members keep sending application messages.
+pub fn simulate_mls_network(
+    bench_config: &BenchConfig,
+    network_config: &NetworkConfig,
+    scenario: &NetworkScenario,
+) -> Result<NetworkReport> {
+    let mut key_service = KeyService::new();
+    key_service.generate(
+        &bench_config.ciphersuite,
+        &bench_config.provider,
+        scenario.members,
+    )?;
+    let mut joiner_service = KeyService::new();
+    joiner_service.generate(
+        &bench_config.ciphersuite,
+        &bench_config.provider,
+        scenario.joiners,
+    )?;
+    let joiner_key_packages = joiner_service.key_packages();
+
+    let (creator, member_states) = create_bare_group_with_member_states(bench_config, &key_service);
+    let group_id = creator.group_id().clone();
+    let epoch = creator.epoch().as_u64();
+    let total = 1 + scenario.members + scenario.joiners;
+
+    let mut participants: Vec<Option<Participant>> = Vec::with_capacity(total);
+    for state in std::iter::once(MemberState {
+        group: creator,
+        signer: bench_config.self_signer.clone(),
+    })
+    .chain(member_states)
+    {
+        participants.push(Some(Participant {
+            state,
+            joined_epoch: epoch,
+            reached: vec![(epoch, 0)],
+        }));
+    }
+    participants.resize_with(total, || None);
+
+    let mut delivery = DeliveryService::new();
+    let initial: Vec<ClientId> = (0..=scenario.members).collect();
+    delivery.register_group(group_id.clone(), &initial, epoch);
+
+    let mut network = Network::new(network_config.clone())?;
+    schedule_sends(&mut network, total, scenario, MlsEvent::Send);
+    for i in 0..scenario.joiners {
+        network.schedule_at((i as u64 + 1) * scenario.commit_interval, MlsEvent::Add(i));
+    }
+
+    let mut report = NetworkReport::default();
+    let mut changes = Vec::new();
+    let mut notified = HashSet::new();
+
+    while let Some(event) = network.next() {
+        match event {
+            MlsEvent::Send(client) => {
+                let Some(participant) = participants[client].as_mut() else {
+                    continue;
+                };
+                if !participant.state.group.is_active() {
+                    continue;
+                }
+                let message = participant.state.group.create_message(
+                    &bench_config.provider,
+                    &participant.state.signer,
+                    b"network message",
+                )?;
+                network.send(
+                    Link::Up(client),
+                    MlsEvent::Upload(client, Upload::Message(message)),
+                );
+            }
+            MlsEvent::Add(i) => {
+                let Some(creator) = participants[CREATOR].as_mut() else {
+                    bail!("Creator is missing");
+                };
+                let group = &mut creator.state.group;
+                let epoch = group.epoch().as_u64();
+                let (commit, welcome, _) = group.add_members(
+                    &bench_config.provider,
+                    &bench_config.self_signer,
+                    &[joiner_key_packages[i].clone()],
+                )?;
+                // The creator is the only committer, so its commits are
+                // always accepted
+                group.merge_pending_commit(&bench_config.provider)?;
+                let new_epoch = group.epoch().as_u64();
+                creator.reached.push((new_epoch, network.now()));
+                changes.push((new_epoch, network.now()));
+
+                let upload = Upload::Commit {
+                    epoch,
+                    commit,
+                    welcome,
+                    ratchet_tree: group.export_ratchet_tree().into(),
+                    joiner: 1 + scenario.members + i,
+                };
+                network.send(Link::Up(CREATOR), MlsEvent::Upload(CREATOR, upload));
+            }
+            MlsEvent::Upload(sender, upload) => {
+                match upload {
+                    Upload::Commit {
+                        epoch,
+                        commit,
+                        welcome,
+                        ratchet_tree,
+                        joiner,
+                    } => {
+                        delivery.submit_commit(&group_id, sender, epoch, &commit)?;
+                        delivery.add_to_group(&group_id, joiner)?;
+                        delivery.send_welcome(&welcome, ratchet_tree, &[joiner]);
+                    }
+                    Upload::Message(message) => {
+                        delivery.send(&group_id, sender, &message)?;
+                    }
+                }
+                notify(
+                    &mut network,
+                    &delivery,
+                    &mut notified,
+                    0..total,
+                    MlsEvent::Download,
+                );
+            }
+            MlsEvent::Download(client) => {
+                notified.remove(&client);
+                let now = network.now();
+                for envelope in delivery.fetch(client) {
+                    match envelope {
+                        Envelope::Welcome {
+                            welcome,
+                            ratchet_tree,
+                        } => {
+                            let group = join_from_welcome(bench_config, &welcome, ratchet_tree)?;
+                            let Some(joiner) =
+                                joiner_service.member_by_signature_key(&own_signature_key(&group))
+                            else {
+                                bail!("Joined with an unknown key package");
+                            };
+                            let epoch = group.epoch().as_u64();
+                            participants[client] = Some(Participant {
+                                state: MemberState {
+                                    group,
+                                    signer: joiner.signature_pair.clone(),
+                                },
+                                joined_epoch: epoch,
+                                reached: vec![(epoch, now)],
+                            });
+                        }
+                        Envelope::Mls { message, .. } => {
+                            let Some(participant) = participants[client].as_mut() else {
+                                continue;
+                            };
+                            let group = &mut participant.state.group;
+                            match receive_message(group, &bench_config.provider, &message) {
+                                Ok(Received::Commit) => {
+                                    participant.reached.push((group.epoch().as_u64(), now));
+                                }
+                                Ok(_) => report.received += 1,
+                                // Only the creator commits, in order, so
+                                // failures are application messages from
+                                // another epoch
+                                Err(_) => {
+                                    report.received += 1;
+                                    report.undecryptable += 1;
+                                }
+                            }
+                        }
+                        _ => bail!("Unexpected envelope"),
+                    }
+                }
+            }
+        }
+    }
+
+    let reached: Vec<_> = participants
+        .into_iter()
+        .flatten()
+        .map(|participant| (participant.joined_epoch, participant.reached))
+        .collect();
+    report.convergence_times = changes
+        .into_iter()
+        .map(|(epoch, changed_at)| convergence_time(reached.iter(), epoch, changed_at))
+        .collect();
+    report.retransmissions = network.retransmissions();
+    Ok(report)
+}
+
+// First byte of every plaintext in the ratchet simulation
+const APPLICATION: u8 = 0;
+/// The creator tells a member about a joiner
+const JOIN_NOTICE: u8 = 1;
+/// The creator tells a joiner which members it has sessions with
+const MEMBER_LIST: u8 = 2;
+
+enum RatchetEvent {
+    Send(ClientId),
+    Add(usize),
+    Upload(ClientId, Vec<(ClientId, RatchetMessage)>),
+    Download(ClientId),
+}
+
+/// Both ends of the pairwise session of every two clients, the lower client
+/// holding the first.
+#[derive(Default)]
+struct Sessions(HashMap<(ClientId, ClientId), (Ratchet<StaticSecret>, Ratchet<StaticSecret>)>);
+
+impl Sessions {
+    fn connect(&mut self, a: ClientId, b: ClientId) {
+        self.0
+            .insert((a.min(b), a.max(b)), RatchetGroup::session_pair());
+    }
+
+    fn end(&mut self, own: ClientId, peer: ClientId) -> Result<&mut Ratchet<StaticSecret>> {
+        let Some(pair) = self.0.get_mut(&(own.min(peer), own.max(peer))) else {
+            bail!("No session between {} and {}", own, peer);
+        };
+        Ok(if own < peer { &mut pair.0 } else { &mut pair.1 })
+    }
+
+    fn encrypt(
+        &mut self,
+        sender: ClientId,
+        recipient: ClientId,
+        kind: u8,
+        content: &[u8],
+    ) -> Result<RatchetMessage> {
+        let plaintext = [&[kind], content].concat();
+        Ok(self
+            .end(sender, recipient)?
+            .ratchet_encrypt(&plaintext, &[]))
+    }
+
+    fn decrypt(
+        &mut self,
+        recipient: ClientId,
+        sender: ClientId,
+        message: &RatchetMessage,
+    ) -> Result<Vec<u8>> {
+        let ratchet = self.end(recipient, sender)?;
+        let (header, ciphertext, nonce) = message;
+        catch_decrypt(|| ratchet.ratchet_decrypt(header, ciphertext, nonce, &[]))
+    }
+}
+
+fn encode_clients(clients: &[ClientId]) -> Vec<u8> {
+    clients
+        .iter()
+        .flat_map(|&client| (client as u64).to_be_bytes())
+        .collect()
+}
+
+fn decode_clients(bytes: &[u8]) -> Result<Vec<ClientId>> {
+    if bytes.len() % 8 != 0 {
+        bail!("Invalid client list");
+    }
+    bytes
+        .chunks(8)
+        .map(|chunk| Ok(u64::from_be_bytes(chunk.try_into()?).try_into()?))
+        .collect()
+}
+
+/// A joiner added by the creator, and who has yet to learn of it.
+struct JoinChange {
+    added_at: u64,
+    pending: HashSet<ClientId>,
+    learned_at: u64,
+}
+
+impl JoinChange {
+    fn learn(&mut self, client: ClientId, now: u64) {
+        if self.pending.remove(&client) {
+            self.learned_at = self.learned_at.max(now);
+        }
+    }
+}
+
+/// Runs a pairwise ratchet group over the simulated network, with the same
+/// traffic as [`simulate_mls_network`]: every member sends each application
+/// message over its session with every other member, and the creator adds
+/// the joiners. Sessions come from prekeys fetched out of band, so adding a
+/// joiner takes a notice to every member and the member list to the joiner.
+pub fn simulate_ratchet_network(
+    network_config: &NetworkConfig,
+    scenario: &NetworkScenario,
+) -> Result<NetworkReport> {
+    let total = 1 + scenario.members + scenario.joiners;
+    let first_joiner = 1 + scenario.members;
+    let mut sessions = Sessions::default();
+    for a in 0..first_joiner {
+        for b in a + 1..first_joiner {
+            sessions.connect(a, b);
+        }
+    }
+    // Members each client knows of and sends to
+    let mut peers: Vec<Vec<ClientId>> = (0..total)
+        .map(|client| {
+            if client < first_joiner {
+                (0..first_joiner).filter(|&peer| peer != client).collect()
+            } else {
+                Vec::new()
+            }
+        })
+        .collect();
+
+    let mut delivery = DeliveryService::new();
+    let mut network = Network::new(network_config.clone())?;
+    schedule_sends(&mut network, total, scenario, RatchetEvent::Send);
+    for i in 0..scenario.joiners {
+        network.schedule_at(
+            (i as u64 + 1) * scenario.commit_interval,
+            RatchetEvent::Add(i),
+        );
+    }
+
+    let mut report = NetworkReport::default();
+    let mut changes: Vec<JoinChange> = Vec::new();
+    let mut notified = HashSet::new();
+
+    while let Some(event) = network.next() {
+        match event {
+            RatchetEvent::Send(client) => {
+                if peers[client].is_empty() {
+                    continue;
+                }
+                let messages = peers[client]
+                    .iter()
+                    .map(|&peer| {
+                        let message =
+                            sessions.encrypt(client, peer, APPLICATION, b"network message")?;
+                        Ok((peer, message))
+                    })
+                    .collect::<Result<_>>()?;
+                network.send(Link::Up(client), RatchetEvent::Upload(client, messages));
+            }
+            RatchetEvent::Add(i) => {
+                let joiner = first_joiner + i;
+                let members = peers[CREATOR].clone();
+                let mut messages = Vec::with_capacity(members.len() + 1);
+                sessions.connect(CREATOR, joiner);
+                for &member in &members {
+                    sessions.connect(member, joiner);
+                    let notice = encode_clients(&[joiner]);
+                    messages.push((
+                        member,
+                        sessions.encrypt(CREATOR, member, JOIN_NOTICE, &notice)?,
+                    ));
+                }
+                let mut member_list = members.clone();
+                member_list.push(CREATOR);
+                messages.push((
+                    joiner,
+                    sessions.encrypt(
+                        CREATOR,
+                        joiner,
+                        MEMBER_LIST,
+                        &encode_clients(&member_list),
+                    )?,
+                ));
+                peers[CREATOR].push(joiner);
+
+                let now = network.now();
+                changes.push(JoinChange {
+                    added_at: now,
+                    pending: members.into_iter().chain([joiner]).collect(),
+                    learned_at: now,
+                });
+                network.send(Link::Up(CREATOR), RatchetEvent::Upload(CREATOR, messages));
+            }
+            RatchetEvent::Upload(sender, messages) => {
+                delivery.send_ratchet(sender, messages);
+                notify(
+                    &mut network,
+                    &delivery,
+                    &mut notified,
+                    0..total,
+                    RatchetEvent::Download,
+                );
+            }
+            RatchetEvent::Download(client) => {
+                notified.remove(&client);
+                let now = network.now();
+                for envelope in delivery.fetch(client) {
+                    let Envelope::Ratchet { sender, message } = envelope else {
+                        bail!("Unexpected envelope");
+                    };
+                    let Ok(plaintext) = sessions.decrypt(client, sender, &message) else {
+                        report.received += 1;
+                        report.undecryptable += 1;
+                        continue;
+                    };
+                    match plaintext.split_first() {
+                        Some((&APPLICATION, _)) => report.received += 1,
+                        Some((&JOIN_NOTICE, content)) => {
+                            for joiner in decode_clients(content)? {
+                                peers[client].push(joiner);
+                                changes[joiner - first_joiner].learn(client, now);
+                            }
+                        }
+                        Some((&MEMBER_LIST, content)) => {
+                            peers[client] = decode_clients(content)?;
+                            changes[client - first_joiner].learn(client, now);
+                        }
+                        _ => bail!("Unknown message"),
+                    }
+                }
+            }
+        }
+    }
+
+    report.convergence_times = changes
+        .into_iter()
+        .map(|change| {
+            change
+                .pending
+                .is_empty()
+                .then(|| change.learned_at - change.added_at)
+        })
+        .collect();
+    report.retransmissions = network.retransmissions();
+    Ok(report)
+}
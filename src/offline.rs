@@ -0,0 +1,236 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use double_ratchet_2::aead::{decrypt, encrypt};
+use openmls::prelude::*;
+
+use crate::delivery::{ClientId, DeliveryService, Envelope};
+use crate::key_service::KeyService;
+use crate::mls::{
+    create_bare_group_with_joined_members, receive_message, BenchConfig, MemberState, CREATOR,
+};
+use crate::ratchet::{generate_random_bytes, RatchetGroup};
+
+// The member coming back online
+const OFFLINE: ClientId = 1;
+
+#[derive(Debug, Default)]
+pub struct CatchUpReport {
+    /// Envelopes waiting in the mailbox of the returning member
+    pub envelopes: usize,
+    pub downloaded_bytes: usize,
+    /// Time the returning member spends processing its backlog
+    pub processing_time: Duration,
+}
+
+// Number of application messages sent in `epoch`, spreading `messages`
+// evenly over `commits + 1` epochs
+fn messages_in_epoch(epoch: usize, commits: usize, messages: usize) -> usize {
+    messages / (commits + 1) + usize::from(epoch < messages % (commits + 1))
+}
+
+// Online members sending the backlog, so it spans several leaves of the
+// secret tree without every member of a big group having to join
+const MAX_SENDERS: usize = 16;
+
+fn check_group_size(group_size: usize) -> Result<()> {
+    if group_size < 2 {
+        bail!("A group of {} has no one to catch up with", group_size);
+    }
+    Ok(())
+}
+
+// Processes everything waiting for an online member, keeping it in the
+// current epoch
+fn process_mailbox(
+    group: &mut MlsGroup,
+    bench_config: &BenchConfig,
+    delivery: &mut DeliveryService,
+    client: ClientId,
+) -> Result<()> {
+    for envelope in delivery.fetch(client) {
+        let Envelope::Mls { message, .. } = envelope else {
+            bail!("Not an MLS message");
+        };
+        receive_message(group, &bench_config.provider, &message)?;
+    }
+    Ok(())
+}
+
+/// A member of a group of `group_size` misses `commits` updates and
+/// `messages` application messages of `message_size` bytes, then fetches
+/// and processes everything at once. The messages rotate through up to 16
+/// online members.
+pub fn mls_catch_up(
+    bench_config: &BenchConfig,
+    group_size: usize,
+    commits: usize,
+    messages: usize,
+    message_size: usize,
+) -> Result<CatchUpReport> {
+    check_group_size(group_size)?;
+    let mut key_service = KeyService::new();
+    key_service.generate(
+        &bench_config.ciphersuite,
+        &bench_config.provider,
+        group_size - 1,
+    )?;
+    let (creator, mut member_states) =
+        create_bare_group_with_joined_members(bench_config, &key_service, MAX_SENDERS);
+    let mut offline = member_states.remove(0);
+
+    let group_id = creator.group_id().clone();
+    // The creator and the joined members after the returning one send
+    let mut online = vec![MemberState {
+        group: creator,
+        signer: bench_config.self_signer.clone(),
+    }];
+    online.extend(member_states);
+    let client = |sender: usize| {
+        if sender == 0 {
+            CREATOR
+        } else {
+            OFFLINE + sender
+        }
+    };
+
+    let mut delivery = DeliveryService::new();
+    let mut members: Vec<ClientId> = (0..online.len()).map(client).collect();
+    members.push(OFFLINE);
+    delivery.register_group(group_id.clone(), &members, online[0].group.epoch().as_u64());
+
+    let message = vec![0u8; message_size];
+    let mut sent = 0;
+    for epoch in 0..=commits {
+        for _ in 0..messages_in_epoch(epoch, commits, messages) {
+            let sender = sent % online.len();
+            sent += 1;
+            let member = &mut online[sender];
+            process_mailbox(
+                &mut member.group,
+                bench_config,
+                &mut delivery,
+                client(sender),
+            )?;
+            let application_message =
+                member
+                    .group
+                    .create_message(&bench_config.provider, &member.signer, &message)?;
+            delivery.send(&group_id, client(sender), &application_message)?;
+        }
+        if epoch < commits {
+            let creator = &mut online[0];
+            process_mailbox(&mut creator.group, bench_config, &mut delivery, CREATOR)?;
+            let epoch = creator.group.epoch().as_u64();
+            let (commit, _, _) = creator
+                .group
+                .self_update(&bench_config.provider, &bench_config.self_signer)?;
+            creator.group.merge_pending_commit(&bench_config.provider)?;
+            delivery.submit_commit(&group_id, CREATOR, epoch, &commit)?;
+        }
+    }
+
+    let envelopes = delivery.fetch(OFFLINE);
+    let mut report = CatchUpReport {
+        envelopes: envelopes.len(),
+        downloaded_bytes: envelopes.iter().map(Envelope::len).sum(),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    for envelope in envelopes {
+        let Envelope::Mls { message, .. } = envelope else {
+            bail!("Not an MLS message");
+        };
+        receive_message(&mut offline.group, &bench_config.provider, &message)?;
+    }
+    report.processing_time = start.elapsed();
+
+    Ok(report)
+}
+
+// The returning member's own view: one session with each other member.
+// Ratchets have no epochs, so commits of the other schemes have no
+// equivalent to catch up on; messages rotate through the senders.
+fn ratchet_backlog(
+    group_size: usize,
+    messages: usize,
+    message_size: usize,
+    optimized: bool,
+) -> Result<CatchUpReport> {
+    check_group_size(group_size)?;
+    let senders = group_size - 1;
+    let mut ratchet_group = RatchetGroup::with_generated_members(senders);
+    let mut delivery = DeliveryService::new();
+
+    let message = vec![0u8; message_size];
+    for i in 0..messages {
+        let session = i % senders;
+        // Sender clients come after the returning member
+        let sender = OFFLINE + 1 + session;
+        if optimized {
+            let key = generate_random_bytes::<32>()?;
+            let (ciphertext, nonce) = encrypt(&key, &message, &[]);
+            delivery.send_payload(sender, &ciphertext, nonce, &[OFFLINE]);
+            let key_message = ratchet_group.encrypt_from_member(session, &key);
+            delivery.send_ratchet(sender, vec![(OFFLINE, key_message)]);
+        } else {
+            let ratchet_message = ratchet_group.encrypt_from_member(session, &message);
+            delivery.send_ratchet(sender, vec![(OFFLINE, ratchet_message)]);
+        }
+    }
+
+    let envelopes = delivery.fetch(OFFLINE);
+    let mut report = CatchUpReport {
+        envelopes: envelopes.len(),
+        downloaded_bytes: delivery.stats().delivered_bytes,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let mut payload = None;
+    for envelope in envelopes {
+        match envelope {
+            Envelope::Payload {
+                ciphertext, nonce, ..
+            } => payload = Some((ciphertext, nonce)),
+            Envelope::Ratchet { sender, message } => {
+                let (header, ciphertext, nonce) = message;
+                let plaintext = ratchet_group.try_decrypt_message(
+                    sender - OFFLINE - 1,
+                    &header,
+                    &ciphertext,
+                    &nonce,
+                )?;
+                if optimized {
+                    let Some((ciphertext, nonce)) = payload.take() else {
+                        bail!("Key envelope without a payload");
+                    };
+                    let key: [u8; 32] = plaintext.as_slice().try_into()?;
+                    decrypt(&key, &ciphertext, &[], &nonce);
+                }
+            }
+            _ => bail!("Unexpected envelope"),
+        }
+    }
+    report.processing_time = start.elapsed();
+
+    Ok(report)
+}
+
+/// Same as [`mls_catch_up`] for pairwise ratchets.
+pub fn pairwise_catch_up(
+    group_size: usize,
+    messages: usize,
+    message_size: usize,
+) -> Result<CatchUpReport> {
+    ratchet_backlog(group_size, messages, message_size, false)
+}
+
+/// Same as [`mls_catch_up`] for pairwise ratchets carrying only a message
+/// key, next to a payload shared by all recipients.
+pub fn optimized_catch_up(
+    group_size: usize,
+    messages: usize,
+    message_size: usize,
+) -> Result<CatchUpReport> {
+    ratchet_backlog(group_size, messages, message_size, true)
+}
@@ -0,0 +1,51 @@
+use anyhow::{bail, Result};
+
+// Marks the end of the message, so padding can be stripped unambiguously
+const PADDING_MARKER: u8 = 0x80;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PaddingPolicy {
+    #[default]
+    None,
+    /// Pads to a multiple of the block size. A block size of zero pads
+    /// nothing, like openmls' `padding_size(0)`.
+    Block(usize),
+    /// Pads to the next power of two
+    PowerOfTwo,
+}
+
+impl PaddingPolicy {
+    fn pads(&self) -> bool { !matches!(self, PaddingPolicy::None | PaddingPolicy::Block(0)) }
+
+    /// Length of a message of `len` bytes after padding.
+    pub fn padded_len(&self, len: usize) -> usize {
+        match *self {
+            PaddingPolicy::None | PaddingPolicy::Block(0) => len,
+            PaddingPolicy::Block(block) => (len + 1).div_ceil(block) * block,
+            PaddingPolicy::PowerOfTwo => (len + 1).next_power_of_two(),
+        }
+    }
+
+    pub fn pad(&self, msg: &[u8]) -> Vec<u8> {
+        if !self.pads() {
+            return msg.to_vec();
+        }
+
+        let mut padded = Vec::with_capacity(self.padded_len(msg.len()));
+        padded.extend_from_slice(msg);
+        padded.push(PADDING_MARKER);
+        padded.resize(self.padded_len(msg.len()), 0);
+        padded
+    }
+
+    pub fn unpad<'a>(&self, padded: &'a [u8]) -> Result<&'a [u8]> {
+        if !self.pads() {
+            return Ok(padded);
+        }
+
+        match padded.iter().rposition(|&byte| byte != 0) {
+            Some(end) if padded[end] == PADDING_MARKER => Ok(&padded[..end]),
+            _ => bail!("Invalid padding"),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use openmls::prelude::*;
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_rust_crypto::OpenMlsRustCrypto;
+
+pub fn propose_add(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+    key_package: &KeyPackage,
+) -> Result<MlsMessageOut> {
+    let (proposal, _) = group.propose_add_member(provider, signer, key_package)?;
+    Ok(proposal)
+}
+
+pub fn propose_remove(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+    member: LeafNodeIndex,
+) -> Result<MlsMessageOut> {
+    let (proposal, _) = group.propose_remove_member(provider, signer, member)?;
+    Ok(proposal)
+}
+
+pub fn propose_update(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+) -> Result<MlsMessageOut> {
+    let (proposal, _) = group.propose_self_update(provider, signer, None)?;
+    Ok(proposal)
+}
+
+/// Proposes removing this member. Another member has to commit it for the
+/// leave to take effect.
+pub fn propose_leave(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+) -> Result<MlsMessageOut> {
+    Ok(group.leave_group(provider, signer)?)
+}
+
+/// Commits every proposal this member has received or sent in the current
+/// epoch, returning the commit and the Welcome if members were added.
+pub fn commit_pending(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+) -> Result<(MlsMessageOut, Option<MlsMessageOut>)> {
+    let (commit, welcome, _) = group.commit_to_pending_proposals(provider, signer)?;
+    group.merge_pending_commit(provider)?;
+    Ok((commit, welcome))
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::{Mutex, RwLock};
+
+use openmls_rust_crypto::RustCrypto;
+use openmls_traits::{
+    key_store::{MlsEntity, OpenMlsKeyStore},
+    random::OpenMlsRand,
+    OpenMlsCryptoProvider,
+};
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+pub struct SeededRand {
+    rng: Mutex<ChaCha20Rng>,
+}
+
+impl SeededRand {
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            rng: Mutex::new(ChaCha20Rng::from_seed(seed)),
+        }
+    }
+}
+
+impl OpenMlsRand for SeededRand {
+    type Error = Infallible;
+
+    fn random_array<const N: usize>(&self) -> Result<[u8; N], Self::Error> {
+        let mut output = [0u8; N];
+        self.rng.lock().unwrap().fill_bytes(&mut output);
+        Ok(output)
+    }
+
+    fn random_vec(&self, len: usize) -> Result<Vec<u8>, Self::Error> {
+        let mut output = vec![0u8; len];
+        self.rng.lock().unwrap().fill_bytes(&mut output);
+        Ok(output)
+    }
+}
+
+/// Provider whose randomness comes from a fixed seed. Keys are written to a
+/// borrowed key store, so they stay visible to the provider that owns it.
+pub struct SeededProvider<'a, K: OpenMlsKeyStore> {
+    crypto: RustCrypto,
+    rand: SeededRand,
+    key_store: &'a K,
+}
+
+impl<'a, K: OpenMlsKeyStore> SeededProvider<'a, K> {
+    pub fn new(seed: [u8; 32], key_store: &'a K) -> Self {
+        Self {
+            crypto: RustCrypto::default(),
+            rand: SeededRand::new(seed),
+            key_store,
+        }
+    }
+}
+
+impl<'a, K: OpenMlsKeyStore> OpenMlsCryptoProvider for SeededProvider<'a, K> {
+    type CryptoProvider = RustCrypto;
+    type RandProvider = SeededRand;
+    type KeyStoreProvider = K;
+
+    fn crypto(&self) -> &Self::CryptoProvider { &self.crypto }
+
+    fn rand(&self) -> &Self::RandProvider { &self.rand }
+
+    fn key_store(&self) -> &Self::KeyStoreProvider { self.key_store }
+}
+
+/// Derives the signing key seed and provider seed of the member at `index`.
+pub fn member_seeds(seed: u64, index: usize) -> ([u8; 32], [u8; 32]) {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    rng.set_stream(index as u64);
+
+    let mut signing_seed = [0u8; 32];
+    let mut provider_seed = [0u8; 32];
+    rng.fill_bytes(&mut signing_seed);
+    rng.fill_bytes(&mut provider_seed);
+    (signing_seed, provider_seed)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SerializationError;
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to serialize key store value")
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+/// In-memory key store that can report what it holds. Values are kept as
+/// JSON, like the openmls memory key store does.
+#[derive(Debug, Default)]
+pub struct CountingKeyStore {
+    values: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl CountingKeyStore {
+    pub fn len(&self) -> usize { self.values.read().unwrap().len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Bytes of all keys and values.
+    pub fn stored_bytes(&self) -> usize {
+        self.values
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum()
+    }
+
+    /// Everything stored, as a client persists it next to its groups.
+    pub fn save(&self) -> anyhow::Result<Vec<u8>> {
+        let values = self.values.read().unwrap();
+        let entries: Vec<_> = values.iter().collect();
+        Ok(serde_json::to_vec(&entries)?)
+    }
+
+    /// Restores a key store saved by [`Self::save`].
+    pub fn load(saved: &[u8]) -> anyhow::Result<Self> {
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = serde_json::from_slice(saved)?;
+        Ok(Self {
+            values: RwLock::new(entries.into_iter().collect()),
+        })
+    }
+}
+
+impl OpenMlsKeyStore for CountingKeyStore {
+    type Error = SerializationError;
+
+    fn store<V: MlsEntity>(&self, k: &[u8], v: &V) -> Result<(), Self::Error> {
+        let value = serde_json::to_vec(v).map_err(|_| SerializationError)?;
+        self.values.write().unwrap().insert(k.to_vec(), value);
+        Ok(())
+    }
+
+    fn read<V: MlsEntity>(&self, k: &[u8]) -> Option<V> {
+        let values = self.values.read().unwrap();
+        serde_json::from_slice(values.get(k)?).ok()
+    }
+
+    fn delete<V: MlsEntity>(&self, k: &[u8]) -> Result<(), Self::Error> {
+        self.values.write().unwrap().remove(k);
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use openmls::prelude::*;
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_rust_crypto::OpenMlsRustCrypto;
+
+/// Writes an external PSK to the provider's key store, returning its id for
+/// use in proposals.
+pub fn store_external_psk(
+    provider: &impl OpenMlsCryptoProvider,
+    ciphersuite: Ciphersuite,
+    id: &[u8],
+    secret: &[u8],
+) -> Result<PreSharedKeyId> {
+    let psk_id = PreSharedKeyId::new(
+        ciphersuite,
+        provider.rand(),
+        Psk::External(ExternalPsk::new(id.to_vec())),
+    )?;
+    psk_id.write_to_key_store(provider, ciphersuite, secret)?;
+    Ok(psk_id)
+}
+
+/// Id of the resumption PSK of one of this group's past epochs. Members keep
+/// these for as many epochs as `number_of_resumption_psks` allows.
+pub fn resumption_psk_id(
+    group: &MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    epoch: GroupEpoch,
+) -> Result<PreSharedKeyId> {
+    Ok(PreSharedKeyId::new(
+        group.ciphersuite(),
+        provider.rand(),
+        Psk::Resumption(ResumptionPsk::new(
+            ResumptionPskUsage::Application,
+            group.group_id().clone(),
+            epoch,
+        )),
+    )?)
+}
+
+/// Commits the given PSK together with adds for `key_packages`. Joiners from
+/// the resulting Welcome need the PSK to join.
+///
+/// openmls 0.5 proposes every kind of PSK through `propose_external_psk`,
+/// `psk_id` decides whether it is an external or a resumption PSK.
+pub fn commit_with_psk(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+    psk_id: PreSharedKeyId,
+    key_packages: &[KeyPackage],
+) -> Result<(MlsMessageOut, Option<MlsMessageOut>)> {
+    group.propose_external_psk(provider, signer, psk_id)?;
+    for key_package in key_packages {
+        group.propose_add_member(provider, signer, key_package)?;
+    }
+
+    let (commit, welcome, _) = group.commit_to_pending_proposals(provider, signer)?;
+    group.merge_pending_commit(provider)?;
+    Ok((commit, welcome))
+}
@@ -1,3 +1,7 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::{anyhow, bail, Result};
 use double_ratchet_2::{aead::encrypt, header::Header, ratchet::Ratchet};
 use rand_chacha::{
     rand_core::{RngCore, SeedableRng},
@@ -5,9 +9,175 @@ use rand_chacha::{
 };
 use x25519_dalek::{PublicKey, StaticSecret};
 
+use crate::padding::PaddingPolicy;
+
+pub type RatchetMessage = (Header<PublicKey>, Vec<u8>, [u8; 12]);
+
+// Public key plus the two message counters
+const HEADER_LEN: usize = 32 + 2 * 8;
+
+/// Runs a decryption of the double ratchet crate, which panics instead of
+/// returning an error on a wrong key or a tampered ciphertext.
+pub(crate) fn catch_decrypt<T>(decrypt: impl FnOnce() -> T) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(decrypt)).map_err(|_| anyhow!("Failed to decrypt"))
+}
+
+/// Size of a ratchet message on the wire.
+pub fn message_len(message: &RatchetMessage) -> usize {
+    HEADER_LEN + message.1.len() + message.2.len()
+}
+
+// Root key, sending and receiving chain keys, own DH key pair, remote public
+// key and the three message counters of one ratchet. An estimate, the ratchet
+// does not expose its state
+const RATCHET_STATE_LEN: usize = 32 + 2 * 32 + 2 * 32 + 32 + 3 * 8;
+
+/// Most message keys a single message may skip. The ratchet itself panics
+/// when a message skips more than its `MAX_SKIP` of 100 keys, so the limit is
+/// the same and no accepted message can reach that panic.
+pub const MAX_SKIPPED_KEYS: usize = 100;
+
+/// Most skipped message keys a session honours, the oldest are evicted first.
+pub const MAX_CACHED_KEYS: usize = 1000;
+
+/// Estimated size of a cached skipped message key: chain public key, message
+/// number and the key itself.
+pub const SKIPPED_KEY_LEN: usize = 32 + 8 + 32;
+
+#[derive(Default)]
+struct ReceivingChain {
+    next: usize,
+    skipped: BTreeSet<usize>,
+}
+
+/// Mirrors the skipped-message-key cache of a receiving ratchet, so gaps can
+/// be bounded before the ratchet itself is touched. The ratchet keeps its
+/// cache private, so the counts are what the mirror expects it to hold. They
+/// change whenever a message was handed to the ratchet, which skips keys and
+/// advances its chain before checking the ciphertext.
+///
+/// The ratchet cannot drop cached keys, so keys evicted past
+/// [`MAX_CACHED_KEYS`] are only refused here and still take up memory.
+#[derive(Default)]
+pub struct SkippedKeys {
+    /// Receiving chains by the sender's ratchet public key
+    chains: HashMap<[u8; 32], ReceivingChain>,
+    /// Public keys of the chains, oldest first
+    order: VecDeque<[u8; 32]>,
+    /// Chains with nothing left to receive, late messages on them are refused
+    finished: HashSet<[u8; 32]>,
+    /// Public key of the chain the ratchet currently receives on
+    current: Option<[u8; 32]>,
+    /// Keys evicted here that the ratchet still holds
+    evicted: usize,
+}
+
+impl SkippedKeys {
+    pub fn len(&self) -> usize { self.chains.values().map(|chain| chain.skipped.len()).sum() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Refuses duplicates, messages whose key was evicted and gaps of more
+    /// than [`MAX_SKIPPED_KEYS`], without changing the mirror.
+    fn check(&self, header: &Header<PublicKey>) -> Result<()> {
+        let chain_key = header.public_key.to_bytes();
+        if self.finished.contains(&chain_key) {
+            bail!("Duplicate or expired message");
+        }
+        let newly_skipped = match self.chains.get(&chain_key) {
+            Some(chain) if header.n < chain.next => {
+                if !chain.skipped.contains(&header.n) {
+                    bail!("Duplicate or expired message");
+                }
+                0
+            }
+            // The ratchet would take a message past the end of an earlier
+            // chain for a new one
+            Some(_) if self.current != Some(chain_key) => {
+                bail!("Message past the end of a finished chain");
+            }
+            Some(chain) => header.n - chain.next,
+            // The ratchet skips the rest of the current chain and the start
+            // of the new one separately, each bounded on its own
+            None => self.previous_chain_skips(header).max(header.n),
+        };
+        if newly_skipped > MAX_SKIPPED_KEYS {
+            bail!("Too many skipped messages");
+        }
+        Ok(())
+    }
+
+    // Opening a new chain, the ratchet skips the rest of the chain it
+    // currently receives on up to the sender's previous chain length. That is
+    // the chain of the last decrypted message: the sender only starts a new
+    // chain after hearing back on the previous one, so no chain is passed over
+    fn previous_chain_skips(&self, header: &Header<PublicKey>) -> usize {
+        self.current
+            .and_then(|key| self.chains.get(&key))
+            .map_or(0, |current| header.pn.saturating_sub(current.next))
+    }
+
+    /// Records a message handed to the ratchet, whether it decrypted or not.
+    fn record(&mut self, header: &Header<PublicKey>) {
+        let chain_key = header.public_key.to_bytes();
+        if !self.chains.contains_key(&chain_key) {
+            if let Some(current) = self.current.and_then(|key| self.chains.get_mut(&key)) {
+                current.skipped.extend(current.next..header.pn);
+                current.next = current.next.max(header.pn);
+            }
+            self.chains.insert(chain_key, ReceivingChain::default());
+            self.order.push_back(chain_key);
+            self.current = Some(chain_key);
+        }
+        let chain = self
+            .chains
+            .get_mut(&chain_key)
+            .expect("Chain was just inserted");
+        if header.n < chain.next {
+            chain.skipped.remove(&header.n);
+        } else {
+            chain.skipped.extend(chain.next..header.n);
+            chain.next = header.n + 1;
+        }
+        self.evict();
+    }
+
+    // Evicts the oldest keys past `MAX_CACHED_KEYS` and retires earlier
+    // chains once nothing is left to receive on them
+    fn evict(&mut self) {
+        let mut len = self.len();
+        while let Some(&oldest) = self.order.front() {
+            if Some(oldest) == self.current {
+                break;
+            }
+            let chain = self.chains.get_mut(&oldest).expect("Ordered chains exist");
+            if len > MAX_CACHED_KEYS && chain.skipped.pop_first().is_some() {
+                len -= 1;
+                self.evicted += 1;
+                continue;
+            }
+            if !chain.skipped.is_empty() {
+                break;
+            }
+            self.chains.remove(&oldest);
+            self.order.pop_front();
+            self.finished.insert(oldest);
+        }
+        // Only the current chain is left
+        if let Some(current) = self.current.and_then(|key| self.chains.get_mut(&key)) {
+            while len > MAX_CACHED_KEYS && current.skipped.pop_first().is_some() {
+                len -= 1;
+                self.evicted += 1;
+            }
+        }
+    }
+}
+
 pub struct RatchetGroup {
     local_ratchets: Vec<Ratchet<StaticSecret>>,
     remote_ratchets: Vec<Ratchet<StaticSecret>>,
+    skipped_keys: Vec<SkippedKeys>,
+    padding: PaddingPolicy,
 }
 
 impl RatchetGroup {
@@ -15,14 +185,23 @@ impl RatchetGroup {
         Self {
             local_ratchets: Vec::new(),
             remote_ratchets: Vec::new(),
+            skipped_keys: Vec::new(),
+            padding: PaddingPolicy::None,
         }
     }
 
     pub fn new_with_members(secrets: Vec<[u8; 32]>) -> Self {
         let ratchets_pairs: (Vec<_>, Vec<_>) = secrets.into_iter().map(Self::init_member).unzip();
+        let skipped_keys = ratchets_pairs
+            .0
+            .iter()
+            .map(|_| SkippedKeys::default())
+            .collect();
         Self {
             local_ratchets: ratchets_pairs.0,
             remote_ratchets: ratchets_pairs.1,
+            skipped_keys,
+            padding: PaddingPolicy::None,
         }
     }
 
@@ -33,35 +212,36 @@ impl RatchetGroup {
         Self::new_with_members(secrets)
     }
 
-    pub fn encrypt_message(&mut self, msg: &[u8]) -> Vec<(Header<PublicKey>, Vec<u8>, [u8; 12])> {
-        self.local_ratchets
-            .iter_mut()
-            .map(|ratchet| ratchet.ratchet_encrypt(msg, &[]))
-            .collect()
+    /// Pads every message plaintext before encryption.
+    pub fn set_padding(&mut self, padding: PaddingPolicy) { self.padding = padding; }
+
+    pub fn encrypt_message(&mut self, msg: &[u8]) -> Vec<RatchetMessage> {
+        let padded = self.padding.pad(msg);
+        self.encrypt_to_all(&padded)
     }
 
     pub fn encrypt_message_efficiently(
         &mut self,
         msg: &[u8],
-    ) -> (
-        Vec<u8>,
-        [u8; 12],
-        Vec<(Header<PublicKey>, Vec<u8>, [u8; 12])>,
-    ) {
+    ) -> (Vec<u8>, [u8; 12], Vec<RatchetMessage>) {
         let secret = generate_random_bytes::<32>().expect("Failed to generate bytes");
-        let (encrypted, nonce) = encrypt(&secret, msg, &[]);
+        let (encrypted, nonce) = encrypt(&secret, &self.padding.pad(msg), &[]);
 
-        let member_ciphertexts = self.encrypt_message(&secret);
+        // Keys have a fixed size, so only the message itself needs padding
+        let member_ciphertexts = self.encrypt_to_all(&secret);
 
         (encrypted, nonce, member_ciphertexts)
     }
 
-    pub fn encrypt_from_member(
-        &mut self,
-        member_index: usize,
-        msg: &[u8],
-    ) -> (Header<PublicKey>, Vec<u8>, [u8; 12]) {
-        self.remote_ratchets[member_index].ratchet_encrypt(msg, &[])
+    fn encrypt_to_all(&mut self, msg: &[u8]) -> Vec<RatchetMessage> {
+        self.local_ratchets
+            .iter_mut()
+            .map(|ratchet| ratchet.ratchet_encrypt(msg, &[]))
+            .collect()
+    }
+
+    pub fn encrypt_from_member(&mut self, member_index: usize, msg: &[u8]) -> RatchetMessage {
+        self.remote_ratchets[member_index].ratchet_encrypt(&self.padding.pad(msg), &[])
     }
 
     pub fn decrypt_message(
@@ -71,7 +251,81 @@ impl RatchetGroup {
         ciphertext: &[u8],
         nonce: &[u8; 12],
     ) -> Vec<u8> {
-        self.local_ratchets[member_index].ratchet_decrypt(header, ciphertext, nonce, &[])
+        self.try_decrypt_message(member_index, header, ciphertext, nonce)
+            .expect("Failed to decrypt message")
+    }
+
+    /// Like [`Self::decrypt_message`], but rejects duplicates and messages
+    /// that would skip more than [`MAX_SKIPPED_KEYS`] keys instead of handing
+    /// them to the ratchet, and returns failed decryptions as errors.
+    pub fn try_decrypt_message(
+        &mut self,
+        member_index: usize,
+        header: &Header<PublicKey>,
+        ciphertext: &[u8],
+        nonce: &[u8; 12],
+    ) -> Result<Vec<u8>> {
+        self.skipped_keys[member_index].check(header)?;
+        let local = &mut self.local_ratchets[member_index];
+        let padded = catch_decrypt(|| local.ratchet_decrypt(header, ciphertext, nonce, &[]));
+        // The ratchet has moved on even if the ciphertext was refused
+        self.skipped_keys[member_index].record(header);
+        Ok(self.padding.unpad(&padded?)?.to_vec())
+    }
+
+    /// Decrypts a message sent by [`Self::encrypt_message`] as the member at
+    /// `member_index` receives it.
+    pub fn decrypt_at_member(
+        &mut self,
+        member_index: usize,
+        message: &RatchetMessage,
+    ) -> Result<Vec<u8>> {
+        let (header, ciphertext, nonce) = message;
+        let remote = &mut self.remote_ratchets[member_index];
+        let padded = catch_decrypt(|| remote.ratchet_decrypt(header, ciphertext, nonce, &[]))?;
+        Ok(self.padding.unpad(&padded)?.to_vec())
+    }
+
+    /// Number of message keys the ratchet is expected to cache for messages
+    /// from `member_index` that have not arrived yet.
+    pub fn skipped_keys(&self, member_index: usize) -> usize {
+        self.skipped_keys[member_index].len()
+    }
+
+    /// Estimate of the state kept for all sessions, including the skipped
+    /// message keys the ratchets are expected to cache. Only the local side of
+    /// each session counts.
+    pub fn state_len(&self) -> usize {
+        let skipped: usize = self
+            .skipped_keys
+            .iter()
+            .map(|keys| keys.len() + keys.evicted)
+            .sum();
+        self.local_ratchets.len() * RATCHET_STATE_LEN + skipped * SKIPPED_KEY_LEN
+    }
+
+    /// Number of sessions, one per other member.
+    pub fn len(&self) -> usize { self.local_ratchets.len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Sends `notice` with fresh key material over every session, e.g. after
+    /// a member was removed, and returns the messages sent. Each peer
+    /// answers, so every session takes a new Diffie-Hellman ratchet step and
+    /// the keys used so far are replaced.
+    pub fn rekey(&mut self, notice: &[u8]) -> Result<Vec<RatchetMessage>> {
+        let secret = generate_random_bytes::<32>()?;
+        let payload = [notice, &secret].concat();
+        let mut sent = Vec::with_capacity(self.len());
+        for member_index in 0..self.len() {
+            let message = self.local_ratchets[member_index].ratchet_encrypt(&payload, &[]);
+            let (header, ciphertext, nonce) = &message;
+            self.remote_ratchets[member_index].ratchet_decrypt(header, ciphertext, nonce, &[]);
+            let (header, ciphertext, nonce) = self.encrypt_from_member(member_index, b"rekeyed");
+            self.try_decrypt_message(member_index, &header, &ciphertext, &nonce)?;
+            sent.push(message);
+        }
+        Ok(sent)
     }
 
     pub fn add_member(&mut self) {
@@ -80,11 +334,34 @@ impl RatchetGroup {
         let mut local_ratchet = Ratchet::<StaticSecret>::init_alice(secret, pk);
         self.local_ratchets.push(local_ratchet);
         self.remote_ratchets.push(remote_ratchet);
+        self.skipped_keys.push(SkippedKeys::default());
     }
 
     pub fn remove_member(&mut self) {
         self.local_ratchets.pop();
         self.remote_ratchets.pop();
+        self.skipped_keys.pop();
+    }
+
+    pub fn remove_member_at(&mut self, member_index: usize) {
+        self.local_ratchets.remove(member_index);
+        self.remote_ratchets.remove(member_index);
+        self.skipped_keys.remove(member_index);
+    }
+
+    /// The member at `member_index` notifies the group that it leaves, then
+    /// its sessions are dropped. The remaining members still have to be
+    /// rekeyed by the caller.
+    pub fn leave(&mut self, member_index: usize, notification: &[u8]) -> Vec<u8> {
+        let (header, ciphertext, nonce) = self.encrypt_from_member(member_index, notification);
+        let received = self.decrypt_message(member_index, &header, &ciphertext, &nonce);
+        self.remove_member_at(member_index);
+        received
+    }
+
+    /// Both ends of a fresh pairwise session, ready to send either way.
+    pub fn session_pair() -> (Ratchet<StaticSecret>, Ratchet<StaticSecret>) {
+        Self::init_member(StaticSecret::random().to_bytes())
     }
 
     fn init_member(secret: [u8; 32]) -> (Ratchet<StaticSecret>, Ratchet<StaticSecret>) {
@@ -0,0 +1,118 @@
+use anyhow::{bail, Result};
+use openmls::prelude::*;
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_rust_crypto::OpenMlsRustCrypto;
+
+use crate::auth::{check_sender, AuthenticationService};
+use crate::mls::{protocol_message, Received};
+use crate::ratchet::{RatchetGroup, RatchetMessage};
+
+/// Sent with the fresh key material when a revoked peer is dropped.
+pub const REMOVAL_NOTICE: &[u8] = b"revoked member removed";
+
+/// Leaves whose credentials `auth` no longer accepts.
+pub fn revoked_members(group: &MlsGroup, auth: &impl AuthenticationService) -> Vec<LeafNodeIndex> {
+    group
+        .members()
+        .filter(|member| {
+            auth.authenticate(&member.credential, &member.signature_key)
+                .is_err()
+        })
+        .map(|member| member.index)
+        .collect()
+}
+
+/// Only the lowest-indexed member still in good standing commits removals, so
+/// members detecting the same revocation do not race each other.
+pub fn is_designated_committer(group: &MlsGroup, auth: &impl AuthenticationService) -> bool {
+    group
+        .members()
+        .filter(|member| {
+            auth.authenticate(&member.credential, &member.signature_key)
+                .is_ok()
+        })
+        .map(|member| member.index)
+        .min()
+        == Some(group.own_leaf_index())
+}
+
+/// Commits the removal of all revoked members, if there are any and this
+/// member is the designated committer.
+pub fn remove_revoked_members(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+    auth: &impl AuthenticationService,
+) -> Result<Option<MlsMessageOut>> {
+    let revoked = revoked_members(group, auth);
+    if revoked.is_empty() || !is_designated_committer(group, auth) {
+        return Ok(None);
+    }
+
+    let (commit, _, _) = group.remove_members(provider, signer, &revoked)?;
+    group.merge_pending_commit(provider)?;
+    Ok(Some(commit))
+}
+
+/// Processes an incoming message, discarding content from revoked senders
+/// and issuing a removal commit when a revoked member is detected. Commits
+/// are always merged so the member stays in the same epoch as the group.
+pub fn receive_enforcing_revocation(
+    group: &mut MlsGroup,
+    provider: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+    message: &MlsMessageOut,
+    auth: &impl AuthenticationService,
+) -> Result<(Option<Received>, Option<MlsMessageOut>)> {
+    let processed = group.process_message(provider, protocol_message(message)?)?;
+    let sender_revoked = check_sender(auth, group, processed.sender()).is_err();
+
+    let received = match processed.into_content() {
+        ProcessedMessageContent::StagedCommitMessage(staged_commit) => {
+            group.merge_staged_commit(provider, *staged_commit)?;
+            Some(Received::Commit)
+        }
+        _ if sender_revoked => None,
+        ProcessedMessageContent::ApplicationMessage(application_message) => {
+            Some(Received::Application(application_message.into_bytes()))
+        }
+        ProcessedMessageContent::ProposalMessage(proposal)
+        | ProcessedMessageContent::ExternalJoinProposalMessage(proposal) => {
+            group.store_pending_proposal(*proposal);
+            Some(Received::Proposal)
+        }
+    };
+
+    let removal = remove_revoked_members(group, provider, signer, auth)?;
+    Ok((received, removal))
+}
+
+/// Pairwise counterpart of [`receive_enforcing_revocation`]. `peers` holds
+/// the credential each session of `group` was set up with. A message from a
+/// revoked peer is discarded, the session with it dropped and all remaining
+/// sessions rekeyed, returning the rekey messages to deliver. There is no
+/// shared state, so every member does this on its own and nobody has to be
+/// designated.
+pub fn receive_ratchet_enforcing_revocation(
+    group: &mut RatchetGroup,
+    peers: &mut Vec<CredentialWithKey>,
+    sender: usize,
+    message: &RatchetMessage,
+    auth: &impl AuthenticationService,
+) -> Result<(Option<Vec<u8>>, Option<Vec<RatchetMessage>>)> {
+    let Some(peer) = peers.get(sender) else {
+        bail!("No session with member {}", sender);
+    };
+    let (header, ciphertext, nonce) = message;
+    let plaintext = group.try_decrypt_message(sender, header, ciphertext, nonce)?;
+    if auth
+        .authenticate(&peer.credential, peer.signature_key.as_slice())
+        .is_ok()
+    {
+        return Ok((Some(plaintext), None));
+    }
+
+    group.remove_member_at(sender);
+    peers.remove(sender);
+    Ok((None, Some(group.rekey(REMOVAL_NOTICE)?)))
+}
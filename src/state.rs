@@ -0,0 +1,216 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{bail, Result};
+use openmls::prelude::*;
+use serde_json::Value;
+
+use crate::credential::{create_keypackage, make_credential};
+use crate::key_service::KeyService;
+use crate::mls::{
+    create_bare_group_with_members, create_group, join_from_welcome_with_provider,
+    protocol_message, BenchConfig,
+};
+use crate::provider::{member_seeds, CountingKeyStore, SeededProvider};
+use crate::ratchet::RatchetGroup;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// System allocator keeping track of the bytes currently allocated. Binaries
+/// measuring heap usage install it as their `#[global_allocator]`, otherwise
+/// all heap sizes are reported as 0.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+pub fn allocated() -> usize { ALLOCATED.load(Ordering::Relaxed) }
+
+/// Runs `f`, returning its result and the heap bytes it still holds.
+pub fn retained_heap<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = allocated();
+    let value = f();
+    (value, allocated().saturating_sub(before))
+}
+
+/// Sizes of the parts of a persisted group, by field, two levels deep.
+pub fn saved_state_components(saved: &[u8]) -> Result<BTreeMap<String, usize>> {
+    let Value::Object(fields) = serde_json::from_slice(saved)? else {
+        bail!("Group state is not an object");
+    };
+    let mut components = BTreeMap::new();
+    for (name, field) in fields {
+        match field {
+            Value::Object(inner) => {
+                for (inner_name, inner_field) in inner {
+                    components.insert(
+                        format!("{}.{}", name, inner_name),
+                        serde_json::to_vec(&inner_field)?.len(),
+                    );
+                }
+            }
+            field => {
+                components.insert(name, serde_json::to_vec(&field)?.len());
+            }
+        }
+    }
+    Ok(components)
+}
+
+#[derive(Debug, Default)]
+pub struct MlsStateSample {
+    /// Application messages sent to the group so far
+    pub messages: usize,
+    pub saved: usize,
+    pub components: BTreeMap<String, usize>,
+    pub ratchet_tree: usize,
+    pub key_store: usize,
+    pub key_store_entries: usize,
+    /// Heap used by the group once loaded from its saved state
+    pub heap: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct RatchetStateSample {
+    pub messages: usize,
+    pub sessions: usize,
+    /// Skipped message keys the sessions are expected to cache, see
+    /// [`RatchetGroup::skipped_keys`]
+    pub skipped_keys: usize,
+    /// See [`RatchetGroup::state_len`]
+    pub estimated: usize,
+    /// Heap used by the group, which holds both ends of every session
+    pub heap: usize,
+}
+
+fn is_lost(message: usize, loss_every: usize) -> bool {
+    loss_every > 0 && (message + 1) % loss_every == 0
+}
+
+/// State of one member of a group of `group_size`, after receiving the
+/// number of application messages in each of `checkpoints`. Every
+/// `loss_every`-th message never arrives, 0 loses none.
+pub fn measure_mls_state(
+    bench_config: &BenchConfig,
+    group_size: usize,
+    checkpoints: &[usize],
+    loss_every: usize,
+) -> Result<Vec<MlsStateSample>> {
+    // The measured member keeps its keys in a store of its own
+    let key_store = CountingKeyStore::default();
+    let (_, provider_seed) = member_seeds(0, group_size);
+    let provider = SeededProvider::new(provider_seed, &key_store);
+    let (credential, signer) =
+        make_credential(&bench_config.ciphersuite, &provider, "Measured".into())?;
+    let key_package = create_keypackage(bench_config.ciphersuite, &provider, credential, &signer)?;
+
+    let mut creator = if group_size > 2 {
+        let mut key_service = KeyService::new();
+        key_service.generate(
+            &bench_config.ciphersuite,
+            &bench_config.provider,
+            group_size - 2,
+        )?;
+        create_bare_group_with_members(bench_config, &key_service)
+    } else {
+        create_group(bench_config)
+    };
+    let (_, welcome, _) = creator.add_members(
+        &bench_config.provider,
+        &bench_config.self_signer,
+        &[key_package],
+    )?;
+    creator.merge_pending_commit(&bench_config.provider)?;
+    let mut member = join_from_welcome_with_provider(
+        &provider,
+        &bench_config.group_config,
+        &welcome,
+        creator.export_ratchet_tree().into(),
+    )?;
+
+    let mut samples = Vec::with_capacity(checkpoints.len());
+    let mut sent = 0;
+    for &checkpoint in checkpoints {
+        while sent < checkpoint {
+            let message = creator.create_message(
+                &bench_config.provider,
+                &bench_config.self_signer,
+                b"measured message",
+            )?;
+            if !is_lost(sent, loss_every) {
+                member.process_message(&provider, protocol_message(&message)?)?;
+            }
+            sent += 1;
+        }
+
+        let mut saved = Vec::new();
+        member.save(&mut saved)?;
+        let (loaded, heap) = retained_heap(|| MlsGroup::load(saved.as_slice()));
+        loaded?;
+        samples.push(MlsStateSample {
+            messages: sent,
+            saved: saved.len(),
+            components: saved_state_components(&saved)?,
+            ratchet_tree: member.export_ratchet_tree().tls_serialized_len(),
+            key_store: key_store.stored_bytes(),
+            key_store_entries: key_store.len(),
+            heap,
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Same as [`measure_mls_state`] for a member holding a pairwise session with
+/// every other member. Messages rotate through the senders.
+pub fn measure_ratchet_state(
+    group_size: usize,
+    checkpoints: &[usize],
+    loss_every: usize,
+) -> Result<Vec<RatchetStateSample>> {
+    if group_size < 2 {
+        bail!("A group of {} has no sessions", group_size);
+    }
+    let sessions = group_size - 1;
+    let (mut ratchet_group, initial_heap) =
+        retained_heap(|| RatchetGroup::with_generated_members(sessions));
+    let base = allocated();
+    let mut samples = Vec::with_capacity(checkpoints.len());
+    let mut sent = 0;
+    for &checkpoint in checkpoints {
+        while sent < checkpoint {
+            let session = sent % sessions;
+            let (header, ciphertext, nonce) =
+                ratchet_group.encrypt_from_member(session, b"measured message");
+            if !is_lost(sent, loss_every) {
+                ratchet_group.try_decrypt_message(session, &header, &ciphertext, &nonce)?;
+            }
+            sent += 1;
+        }
+
+        samples.push(RatchetStateSample {
+            messages: sent,
+            sessions,
+            skipped_keys: (0..sessions)
+                .map(|session| ratchet_group.skipped_keys(session))
+                .sum(),
+            estimated: ratchet_group.state_len(),
+            heap: (initial_heap + allocated()).saturating_sub(base),
+        });
+    }
+
+    Ok(samples)
+}
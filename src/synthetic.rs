@@ -0,0 +1,208 @@
+use anyhow::{bail, Result};
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+use rand_distr::{Distribution, Exp, LogNormal, Zipf};
+
+use crate::workload::{Action, Event, MemberId, Workload};
+
+const HOUR: f64 = 60.0 * 60.0 * 1000.0;
+
+// Groups never shrink below a pair through churn
+const MIN_GROUP_SIZE: usize = 2;
+
+#[derive(Clone, Copy, Debug)]
+pub enum GroupSizeDistribution {
+    Constant(usize),
+    /// Between a minimum and a maximum, both inclusive
+    Uniform(usize, usize),
+    /// Many small groups and a few large ones, up to a maximum size
+    Zipf {
+        max: usize,
+        exponent: f64,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SizeDistribution {
+    Constant(usize),
+    Uniform(usize, usize),
+    /// Long-tailed, around a median size in bytes
+    LogNormal {
+        median: f64,
+        sigma: f64,
+    },
+}
+
+/// Parameters of generated group chat workloads. Rates are per group and
+/// hour, events are drawn as Poisson processes.
+#[derive(Clone, Debug)]
+pub struct WorkloadConfig {
+    pub group_size: GroupSizeDistribution,
+    pub message_size: SizeDistribution,
+    pub messages_per_hour: f64,
+    /// Zipf exponent of sender activity, 0 lets all members send equally
+    pub sender_exponent: f64,
+    /// Joins and leaves
+    pub churn_per_hour: f64,
+    pub updates_per_hour: f64,
+    /// Length of each workload in milliseconds
+    pub duration: u64,
+    pub seed: u64,
+}
+
+impl WorkloadConfig {
+    /// Large groups where few members say anything, like an announcement
+    /// channel.
+    pub fn large_low_activity() -> Self {
+        WorkloadConfig {
+            group_size: GroupSizeDistribution::Uniform(200, 1000),
+            message_size: SizeDistribution::LogNormal {
+                median: 200.0,
+                sigma: 1.5,
+            },
+            messages_per_hour: 5.0,
+            sender_exponent: 2.0,
+            churn_per_hour: 10.0,
+            updates_per_hour: 1.0,
+            duration: 24 * HOUR as u64,
+            seed: 0,
+        }
+    }
+
+    /// Small groups where everyone talks.
+    pub fn small_chatty() -> Self {
+        WorkloadConfig {
+            group_size: GroupSizeDistribution::Uniform(3, 10),
+            message_size: SizeDistribution::LogNormal {
+                median: 60.0,
+                sigma: 1.0,
+            },
+            messages_per_hour: 300.0,
+            sender_exponent: 0.5,
+            churn_per_hour: 0.1,
+            updates_per_hour: 2.0,
+            duration: 24 * HOUR as u64,
+            seed: 0,
+        }
+    }
+}
+
+// Between `min` and `max`, both inclusive
+fn sample_uniform(rng: &mut ChaCha20Rng, min: usize, max: usize) -> Result<usize> {
+    if min > max {
+        bail!("Uniform range {}..={} is empty", min, max);
+    }
+    let span = (max - min) as u64;
+    Ok(min + (rng.next_u64() % span.saturating_add(1)) as usize)
+}
+
+fn sample_group_size(rng: &mut ChaCha20Rng, distribution: GroupSizeDistribution) -> Result<usize> {
+    let size = match distribution {
+        GroupSizeDistribution::Constant(size) => size,
+        GroupSizeDistribution::Uniform(min, max) => sample_uniform(rng, min, max)?,
+        GroupSizeDistribution::Zipf { max, exponent } => {
+            Zipf::new(max as u64, exponent)?.sample(rng) as usize
+        }
+    };
+    Ok(size.max(MIN_GROUP_SIZE))
+}
+
+fn sample_size(rng: &mut ChaCha20Rng, distribution: SizeDistribution) -> Result<usize> {
+    let size = match distribution {
+        SizeDistribution::Constant(size) => size,
+        SizeDistribution::Uniform(min, max) => sample_uniform(rng, min, max)?,
+        SizeDistribution::LogNormal { median, sigma } => {
+            LogNormal::new(median.ln(), sigma)?.sample(rng) as usize
+        }
+    };
+    Ok(size.max(1))
+}
+
+// Times in milliseconds of a Poisson process with `per_hour` events
+fn arrivals(rng: &mut ChaCha20Rng, per_hour: f64, duration: u64) -> Result<Vec<u64>> {
+    if per_hour <= 0.0 {
+        return Ok(Vec::new());
+    }
+    let interarrival = Exp::new(per_hour / HOUR)?;
+    let mut times = Vec::new();
+    let mut time = interarrival.sample(rng);
+    while time < duration as f64 {
+        times.push(time as u64);
+        time += interarrival.sample(rng);
+    }
+    Ok(times)
+}
+
+/// Generates a single group's workload, seeded by `config.seed` and
+/// `index`.
+pub fn generate(config: &WorkloadConfig, index: u64) -> Result<Workload> {
+    let mut rng = ChaCha20Rng::seed_from_u64(config.seed);
+    rng.set_stream(index);
+
+    let group_size = sample_group_size(&mut rng, config.group_size)?;
+    let mut members: Vec<MemberId> = (1..=group_size as MemberId).collect();
+    let mut next_member = group_size as MemberId + 1;
+    let mut events: Vec<Event> = members
+        .iter()
+        .map(|&member| Event {
+            time: 0,
+            action: Action::Join { member },
+        })
+        .collect();
+
+    // Kind of each timed event: 0 message, 1 churn, 2 update
+    let mut timeline: Vec<(u64, u8)> = Vec::new();
+    for (kind, per_hour) in [
+        config.messages_per_hour,
+        config.churn_per_hour,
+        config.updates_per_hour,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let times = arrivals(&mut rng, per_hour, config.duration)?;
+        timeline.extend(times.into_iter().map(|time| (time, kind as u8)));
+    }
+    timeline.sort();
+
+    for (time, kind) in timeline {
+        let action = match kind {
+            0 => {
+                // Members earlier in the list are the more active ones
+                let rank =
+                    Zipf::new(members.len() as u64, config.sender_exponent)?.sample(&mut rng);
+                Action::Send {
+                    member: members[rank as usize - 1],
+                    size: sample_size(&mut rng, config.message_size)?,
+                }
+            }
+            1 if members.len() > MIN_GROUP_SIZE && rng.next_u32() % 2 == 0 => {
+                let index = rng.next_u64() as usize % members.len();
+                Action::Leave {
+                    member: members.remove(index),
+                }
+            }
+            1 => {
+                let member = next_member;
+                next_member += 1;
+                members.push(member);
+                Action::Join { member }
+            }
+            _ => Action::Update {
+                member: members[rng.next_u64() as usize % members.len()],
+            },
+        };
+        events.push(Event { time, action });
+    }
+
+    Ok(Workload { events })
+}
+
+/// Generates workloads of `groups` independent groups.
+pub fn generate_many(config: &WorkloadConfig, groups: usize) -> Result<Vec<Workload>> {
+    (0..groups as u64)
+        .map(|index| generate(config, index))
+        .collect()
+}
@@ -0,0 +1,521 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use openmls::prelude::*;
+use openmls::treesync::RatchetTreeIn;
+use openmls_rust_crypto::OpenMlsRustCrypto;
+use serde::{Deserialize, Serialize};
+
+use crate::credential::{create_keypackage, make_credential};
+use crate::delivery::{ClientId, DeliveryService};
+use crate::mls::{
+    create_group, join_from_welcome, receive_message, saved_state_len, BenchConfig, MemberState,
+    CREATOR,
+};
+use crate::ratchet::{RatchetGroup, RatchetMessage};
+
+// Size of a membership instruction sent over pairwise sessions, as in the
+// remove benchmark
+const INSTRUCTION_LEN: usize = 512;
+
+/// Anonymized member identifier in a trace.
+pub type MemberId = u32;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Join {
+        member: MemberId,
+    },
+    Leave {
+        member: MemberId,
+    },
+    /// An application message of `size` bytes
+    Send {
+        member: MemberId,
+        size: usize,
+    },
+    Update {
+        member: MemberId,
+    },
+    /// Messages for the member are queued until it comes back online
+    Offline {
+        member: MemberId,
+    },
+    Online {
+        member: MemberId,
+    },
+}
+
+impl Action {
+    pub fn member(&self) -> MemberId {
+        match self {
+            Action::Join { member }
+            | Action::Leave { member }
+            | Action::Send { member, .. }
+            | Action::Update { member }
+            | Action::Offline { member }
+            | Action::Online { member } => *member,
+        }
+    }
+}
+
+/// One line of a workload file, e.g.
+/// `{"time":1500,"action":"send","member":3,"size":120}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    /// Milliseconds since the start of the trace
+    pub time: u64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+/// A timed list of events in a single group, stored as JSON lines.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Workload {
+    pub events: Vec<Event>,
+}
+
+impl Workload {
+    /// Reads a workload, skipping empty lines. Events have to be sorted by
+    /// time.
+    pub fn read(reader: impl BufRead) -> Result<Self> {
+        let mut events: Vec<Event> = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: Event = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid event on line {}", i + 1))?;
+            if events.last().is_some_and(|last| last.time > event.time) {
+                bail!("Event on line {} is out of order", i + 1);
+            }
+            events.push(event);
+        }
+        Ok(Workload { events })
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<()> {
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a protocol puts on the wire for one operation, delivered by the
+/// replay engine through a [`DeliveryService`].
+#[derive(Default)]
+pub struct Traffic {
+    /// Fanned out by the server to the rest of the group
+    pub fan_out: Option<MlsMessageOut>,
+    /// Whether the creator sent `fan_out` rather than the acting member, as
+    /// with the commits adding and removing members
+    pub by_creator: bool,
+    /// Welcome for the joining member
+    pub welcome: Option<(MlsMessageOut, RatchetTreeIn)>,
+    /// Pairwise messages, each for a member or, with `None`, for the creator
+    pub pairwise: Vec<(Option<MemberId>, RatchetMessage)>,
+    /// Ciphertext stored once for the rest of the group
+    pub payload: Option<(Vec<u8>, [u8; 12])>,
+}
+
+/// A group messaging protocol the replay engine can drive. Every operation
+/// is performed by one replaying client on behalf of `member`, so CPU time
+/// is that of the acting member. The replaying client created the group and
+/// stays a member that is not part of the trace.
+pub trait Protocol {
+    fn name(&self) -> &'static str;
+
+    fn join(&mut self, member: MemberId) -> Result<Traffic>;
+
+    fn leave(&mut self, member: MemberId) -> Result<Traffic>;
+
+    fn send(&mut self, member: MemberId, size: usize) -> Result<Traffic>;
+
+    fn update(&mut self, member: MemberId) -> Result<Traffic>;
+
+    /// State the replaying client persists for the group.
+    fn storage(&mut self) -> Result<usize>;
+}
+
+/// Server side numbers are those of the [`DeliveryService`] the traffic went
+/// through.
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub events: usize,
+    pub cpu_time: Duration,
+    pub uploaded_bytes: usize,
+    pub downloaded_bytes: usize,
+    /// Mailbox writes, one per envelope and recipient, the group creator
+    /// included
+    pub queue_writes: usize,
+    /// Bytes written to mailboxes, shared payloads counted once
+    pub stored_bytes: usize,
+    /// Most bytes waiting in mailboxes at the same time
+    pub peak_server_storage: usize,
+    pub peak_client_storage: usize,
+    pub final_client_storage: usize,
+}
+
+// Clients of the trace members, the creator being client 0
+fn client(member: MemberId) -> ClientId { member as ClientId + 1 }
+
+/// Replays `workload` against `protocol`. Online members and the creator fetch
+/// their mailbox after every event, offline members once they come back.
+pub fn replay(protocol: &mut impl Protocol, workload: &Workload) -> Result<ReplayReport> {
+    let mut report = ReplayReport::default();
+    let mut members = HashSet::new();
+    let mut offline = HashSet::new();
+
+    // The replaying client orders every commit itself, so the server never
+    // has to reject one and all traffic is fanned out as is
+    let group_id = GroupId::from_slice(b"replay");
+    let mut delivery = DeliveryService::new();
+    delivery.register_group(group_id.clone(), &[CREATOR], 0);
+
+    for event in &workload.events {
+        let member = event.action.member();
+        let start = Instant::now();
+        let traffic = match event.action {
+            Action::Join { .. } => {
+                if !members.insert(member) {
+                    bail!("Member {} joined twice", member);
+                }
+                protocol.join(member)?
+            }
+            Action::Leave { .. } => {
+                if !members.remove(&member) {
+                    bail!("Member {} left without joining", member);
+                }
+                offline.remove(&member);
+                delivery.remove_from_group(&group_id, client(member))?;
+                delivery.discard(client(member));
+                protocol.leave(member)?
+            }
+            Action::Send { .. } | Action::Update { .. } if !members.contains(&member) => {
+                bail!("Member {} is not in the group", member);
+            }
+            Action::Send { size, .. } => protocol.send(member, size)?,
+            Action::Update { .. } => protocol.update(member)?,
+            Action::Offline { .. } => {
+                offline.insert(member);
+                Traffic::default()
+            }
+            Action::Online { .. } => {
+                offline.remove(&member);
+                Traffic::default()
+            }
+        };
+        report.cpu_time += start.elapsed();
+        report.events += 1;
+
+        let sender = client(member);
+        if let Some(message) = &traffic.fan_out {
+            let fan_out_sender = if traffic.by_creator { CREATOR } else { sender };
+            delivery.send(&group_id, fan_out_sender, message)?;
+        }
+        if let Some((welcome, ratchet_tree)) = traffic.welcome {
+            delivery.send_welcome(&welcome, ratchet_tree, &[sender]);
+        }
+        if let Some((ciphertext, nonce)) = &traffic.payload {
+            // The group creator receives everything too
+            let recipients: Vec<_> = members
+                .iter()
+                .filter(|&&recipient| recipient != member)
+                .map(|&recipient| client(recipient))
+                .chain([CREATOR])
+                .collect();
+            delivery.send_payload(sender, ciphertext, *nonce, &recipients);
+        }
+        delivery.send_ratchet(
+            sender,
+            traffic
+                .pairwise
+                .into_iter()
+                .map(|(recipient, message)| (recipient.map_or(CREATOR, client), message))
+                .collect(),
+        );
+        if matches!(event.action, Action::Join { .. }) {
+            delivery.add_to_group(&group_id, sender)?;
+        }
+
+        delivery.fetch(CREATOR);
+        for &recipient in members.difference(&offline) {
+            delivery.fetch(client(recipient));
+        }
+
+        if matches!(event.action, Action::Join { .. } | Action::Leave { .. }) {
+            let storage = protocol.storage()?;
+            report.peak_client_storage = report.peak_client_storage.max(storage);
+        }
+    }
+    report.final_client_storage = protocol.storage()?;
+
+    let stats = delivery.stats();
+    report.uploaded_bytes = stats.uploaded_bytes;
+    report.downloaded_bytes = stats.delivered_bytes;
+    report.queue_writes = stats.queue_writes;
+    report.stored_bytes = stats.written_bytes;
+    report.peak_server_storage = stats.peak_stored_bytes;
+
+    Ok(report)
+}
+
+// A member's view of the group, behind on the commits it missed
+struct MlsMember {
+    state: MemberState,
+    missed: Vec<MlsMessageOut>,
+}
+
+// Brings `member` up to date and returns its state
+fn catch_up<'a>(
+    members: &'a mut HashMap<MemberId, MlsMember>,
+    provider: &OpenMlsRustCrypto,
+    member: MemberId,
+) -> Result<&'a mut MemberState> {
+    let Some(acting) = members.get_mut(&member) else {
+        bail!("Member {} is not in the group", member);
+    };
+    for commit in acting.missed.drain(..) {
+        receive_message(&mut acting.state.group, provider, &commit)?;
+    }
+    Ok(&mut acting.state)
+}
+
+/// TreeKEM through openmls. Every member keeps its own group state and only
+/// processes the commits it missed once it acts, which counts towards the
+/// CPU time of its operation.
+pub struct MlsProtocol {
+    config: BenchConfig,
+    group: MlsGroup,
+    members: HashMap<MemberId, MlsMember>,
+}
+
+impl MlsProtocol {
+    pub fn new(config: BenchConfig) -> Self {
+        let group = create_group(&config);
+        MlsProtocol {
+            config,
+            group,
+            members: HashMap::new(),
+        }
+    }
+
+    fn leaf_index(&self, member: MemberId) -> Result<LeafNodeIndex> {
+        let Some(state) = self.members.get(&member) else {
+            bail!("Member {} is not in the group", member);
+        };
+        Ok(state.state.group.own_leaf_index())
+    }
+
+    // Every member but `sender` has to process `commit`
+    fn distribute(&mut self, sender: Option<MemberId>, commit: &MlsMessageOut) {
+        for (&member, state) in self.members.iter_mut() {
+            if Some(member) != sender {
+                state.missed.push(commit.clone());
+            }
+        }
+    }
+}
+
+impl Protocol for MlsProtocol {
+    fn name(&self) -> &'static str { "TreeKEM" }
+
+    fn join(&mut self, member: MemberId) -> Result<Traffic> {
+        let (credential, signer) = make_credential(
+            &self.config.ciphersuite,
+            &self.config.provider,
+            format!("Member {}", member),
+        )?;
+        let key_package = create_keypackage(
+            self.config.ciphersuite,
+            &self.config.provider,
+            credential,
+            &signer,
+        )?;
+
+        let (commit, welcome, _) = self.group.add_members(
+            &self.config.provider,
+            &self.config.self_signer,
+            &[key_package],
+        )?;
+        self.group.merge_pending_commit(&self.config.provider)?;
+        self.distribute(None, &commit);
+
+        let ratchet_tree: RatchetTreeIn = self.group.export_ratchet_tree().into();
+        let group = join_from_welcome(&self.config, &welcome, ratchet_tree.clone())?;
+        self.members.insert(
+            member,
+            MlsMember {
+                state: MemberState { group, signer },
+                missed: Vec::new(),
+            },
+        );
+        Ok(Traffic {
+            fan_out: Some(commit),
+            by_creator: true,
+            welcome: Some((welcome, ratchet_tree)),
+            ..Default::default()
+        })
+    }
+
+    fn leave(&mut self, member: MemberId) -> Result<Traffic> {
+        let leaf = self.leaf_index(member)?;
+        let (commit, _, _) =
+            self.group
+                .remove_members(&self.config.provider, &self.config.self_signer, &[leaf])?;
+        self.group.merge_pending_commit(&self.config.provider)?;
+        self.members.remove(&member);
+        self.distribute(None, &commit);
+        Ok(Traffic {
+            fan_out: Some(commit),
+            by_creator: true,
+            ..Default::default()
+        })
+    }
+
+    fn send(&mut self, member: MemberId, size: usize) -> Result<Traffic> {
+        let provider = &self.config.provider;
+        let acting = catch_up(&mut self.members, provider, member)?;
+        let message = acting
+            .group
+            .create_message(provider, &acting.signer, &vec![0u8; size])?;
+        Ok(Traffic {
+            fan_out: Some(message),
+            ..Default::default()
+        })
+    }
+
+    fn update(&mut self, member: MemberId) -> Result<Traffic> {
+        let provider = &self.config.provider;
+        let acting = catch_up(&mut self.members, provider, member)?;
+        let (commit, _, _) = acting.group.self_update(provider, &acting.signer)?;
+        acting.group.merge_pending_commit(provider)?;
+        // The creator stays current, it commits the membership changes
+        receive_message(&mut self.group, &self.config.provider, &commit)?;
+        self.distribute(Some(member), &commit);
+        Ok(Traffic {
+            fan_out: Some(commit),
+            ..Default::default()
+        })
+    }
+
+    fn storage(&mut self) -> Result<usize> { saved_state_len(&mut self.group) }
+}
+
+/// Pairwise double ratchets, optionally sending the message once under a
+/// fresh key and only the key pairwise.
+pub struct RatchetProtocol {
+    group: RatchetGroup,
+    // Member owning each session, in session order
+    sessions: Vec<MemberId>,
+    optimized: bool,
+}
+
+impl RatchetProtocol {
+    pub fn new(optimized: bool) -> Self {
+        RatchetProtocol {
+            group: RatchetGroup::new(),
+            sessions: Vec::new(),
+            optimized,
+        }
+    }
+
+    // Addresses one message per session to its member, the acting member's
+    // own session standing in for the creator
+    fn address(
+        &self,
+        actor: MemberId,
+        messages: Vec<RatchetMessage>,
+    ) -> Vec<(Option<MemberId>, RatchetMessage)> {
+        self.sessions
+            .iter()
+            .map(|&member| (member != actor).then_some(member))
+            .zip(messages)
+            .collect()
+    }
+}
+
+impl Protocol for RatchetProtocol {
+    fn name(&self) -> &'static str {
+        if self.optimized {
+            "Optimized Ratchet"
+        } else {
+            "Pairwise Ratchet"
+        }
+    }
+
+    // Sessions are set up from prekeys fetched out of band, the members
+    // learn of the joiner from an instruction sent over their sessions
+    fn join(&mut self, member: MemberId) -> Result<Traffic> {
+        self.group.add_member();
+        self.sessions.push(member);
+        let instructions = self.group.encrypt_message(&[0u8; INSTRUCTION_LEN]);
+        Ok(Traffic {
+            pairwise: self.address(member, instructions),
+            ..Default::default()
+        })
+    }
+
+    // The leaver notifies the group, then the rest rekeys with fresh key
+    // material
+    fn leave(&mut self, member: MemberId) -> Result<Traffic> {
+        let Some(index) = self.sessions.iter().position(|&m| m == member) else {
+            bail!("Member {} is not in the group", member);
+        };
+        let notice = self
+            .group
+            .encrypt_from_member(index, &[0u8; INSTRUCTION_LEN]);
+        let (header, ciphertext, nonce) = &notice;
+        self.group
+            .try_decrypt_message(index, header, ciphertext, nonce)?;
+        self.group.remove_member_at(index);
+        self.sessions.remove(index);
+
+        let rekey = self.group.rekey(&[0u8; INSTRUCTION_LEN])?;
+        // The notice goes pairwise to every remaining member and the creator,
+        // the creator rekeys with the remaining members
+        let mut pairwise: Vec<_> = self
+            .sessions
+            .iter()
+            .map(|&recipient| Some(recipient))
+            .chain([None])
+            .map(|recipient| (recipient, notice.clone()))
+            .collect();
+        pairwise.extend(self.address(member, rekey));
+        Ok(Traffic {
+            pairwise,
+            ..Default::default()
+        })
+    }
+
+    // The sender has a session with every other member and the creator, as
+    // many as the creator's own, which stand in for them
+    fn send(&mut self, member: MemberId, size: usize) -> Result<Traffic> {
+        let message = vec![0u8; size];
+        if self.optimized {
+            let (ciphertext, nonce, keys) = self.group.encrypt_message_efficiently(&message);
+            Ok(Traffic {
+                pairwise: self.address(member, keys),
+                payload: Some((ciphertext, nonce)),
+                ..Default::default()
+            })
+        } else {
+            let messages = self.group.encrypt_message(&message);
+            Ok(Traffic {
+                pairwise: self.address(member, messages),
+                ..Default::default()
+            })
+        }
+    }
+
+    // Ratchets heal with every message, there is nothing to update
+    fn update(&mut self, _member: MemberId) -> Result<Traffic> { Ok(Traffic::default()) }
+
+    fn storage(&mut self) -> Result<usize> { Ok(self.group.state_len()) }
+}
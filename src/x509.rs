@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use ed25519_dalek::{pkcs8::EncodePrivateKey, SigningKey};
+use openmls::prelude::Credential;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair, PKCS_ED25519,
+};
+use tls_codec::{Deserialize, Serialize, VLBytes};
+
+pub struct TestCa {
+    certificate: Certificate,
+    der: Vec<u8>,
+}
+
+impl TestCa {
+    pub fn new(name: &str) -> Result<Self> {
+        let mut params = CertificateParams::new(Vec::new());
+        params.alg = &PKCS_ED25519;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+
+        let certificate = Certificate::from_params(params)?;
+        let der = certificate.serialize_der()?;
+        Ok(Self { certificate, der })
+    }
+
+    /// Issues a leaf certificate for `name` binding the given signing key.
+    pub fn issue(&self, name: &str, signing_key: &SigningKey) -> Result<Vec<u8>> {
+        let pkcs8 = signing_key
+            .to_pkcs8_der()
+            .context("Failed to encode signing key")?;
+        let mut params = CertificateParams::new(Vec::new());
+        params.alg = &PKCS_ED25519;
+        params.distinguished_name.push(DnType::CommonName, name);
+        params.key_pair = Some(KeyPair::from_der(pkcs8.as_bytes())?);
+
+        let leaf = Certificate::from_params(params)?;
+        Ok(leaf.serialize_der_with_signer(&self.certificate)?)
+    }
+
+    pub fn certificate_der(&self) -> &[u8] { &self.der }
+}
+
+/// Encodes a certificate chain, leaf first, as a TLS vector of opaque
+/// certificates.
+pub fn encode_chain(chain: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let chain: Vec<VLBytes> = chain.iter().map(|der| der.clone().into()).collect();
+    Ok(chain.tls_serialize_detached()?)
+}
+
+pub fn decode_chain(encoded: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let chain = Vec::<VLBytes>::tls_deserialize(&mut &encoded[..])?;
+    Ok(chain
+        .into_iter()
+        .map(|der| der.as_slice().to_vec())
+        .collect())
+}
+
+/// Certificates, leaf first, of a credential made by
+/// [`crate::credential::make_x509_credential`].
+pub fn credential_chain(credential: &Credential) -> Result<Vec<Vec<u8>>> {
+    decode_chain(credential.identity())
+}
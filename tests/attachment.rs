@@ -0,0 +1,59 @@
+mod common;
+
+use openmls_test::attachment::{
+    open_mls_attachment, open_ratchet_attachment, send_mls_attachment, send_ratchet_attachment,
+};
+use openmls_test::mls::{
+    create_bare_group_with_member_states, receive_message, BenchConfig, Received,
+};
+use openmls_test::ratchet::RatchetGroup;
+
+use common::populated_key_service;
+
+const ATTACHMENT: &[u8] = b"attachment contents";
+
+#[test]
+fn mls_attachment_roundtrips() {
+    let config = BenchConfig::default();
+    let key_service = populated_key_service(&config, 2);
+    let (mut group, mut members) = create_bare_group_with_member_states(&config, &key_service);
+
+    let (blob, message) =
+        send_mls_attachment(&mut group, &config, ATTACHMENT).expect("Failed to send attachment");
+    let member = &mut members[0];
+    let Received::Application(reference) =
+        receive_message(&mut member.group, &config.provider, &message)
+            .expect("Failed to receive reference")
+    else {
+        panic!("Expected an application message");
+    };
+
+    let opened = open_mls_attachment(&member.group, &config.provider, &reference, &blob)
+        .expect("Failed to open attachment");
+    assert_eq!(opened, ATTACHMENT);
+
+    let mut tampered = blob.clone();
+    tampered[0] ^= 1;
+    assert!(open_mls_attachment(&member.group, &config.provider, &reference, &tampered).is_err());
+    assert!(open_mls_attachment(&member.group, &config.provider, &reference, &[]).is_err());
+}
+
+#[test]
+fn ratchet_attachment_roundtrips() {
+    let mut ratchet_group = RatchetGroup::with_generated_members(2);
+
+    let (blob, messages) =
+        send_ratchet_attachment(&mut ratchet_group, ATTACHMENT).expect("Failed to send attachment");
+    assert_eq!(messages.len(), 2);
+    for (member_index, message) in messages.iter().enumerate() {
+        let reference = ratchet_group
+            .decrypt_at_member(member_index, message)
+            .expect("Failed to receive reference");
+        let opened = open_ratchet_attachment(&reference, &blob).expect("Failed to open attachment");
+        assert_eq!(opened, ATTACHMENT);
+
+        let mut tampered = blob.clone();
+        tampered[0] ^= 1;
+        assert!(open_ratchet_attachment(&reference, &tampered).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use anyhow::Result;
+use openmls::prelude::KeyPackage;
+use openmls_test::auth::{auth_error, check_key_package, AuthError, AuthenticationService};
+use openmls_test::key_service::MemberData;
+use openmls_test::mls::{create_authenticated_group_with_members, BenchConfig};
+
+use common::populated_key_service;
+
+fn published(member: &MemberData) -> &KeyPackage {
+    member
+        .key_package
+        .as_ref()
+        .expect("Key package was collected")
+}
+
+fn expect_auth_error<T>(result: Result<T>, expected: AuthError) {
+    let Err(error) = result else {
+        panic!("Expected {:?}, got success", expected);
+    };
+    assert_eq!(auth_error(&error), Some(&expected), "{:#}", error);
+}
+
+#[test]
+fn accepts_registered_members() {
+    let config = BenchConfig::default();
+    let mut key_service = populated_key_service(&config, 3);
+    key_service.trust(&config.self_credential);
+
+    create_authenticated_group_with_members(&config, &key_service, &key_service)
+        .expect("Registered members should be accepted");
+}
+
+#[test]
+fn rejects_unknown_key_package() {
+    let config = BenchConfig::default();
+    let key_service = populated_key_service(&config, 3);
+    let mut registry = populated_key_service(&config, 3);
+    registry.trust(&config.self_credential);
+
+    for member in key_service.all_data() {
+        expect_auth_error(
+            check_key_package(&registry, published(member)),
+            AuthError::UnknownSignatureKey,
+        );
+    }
+    expect_auth_error(
+        create_authenticated_group_with_members(&config, &key_service, &registry),
+        AuthError::UnknownSignatureKey,
+    );
+}
+
+#[test]
+fn rejects_untrusted_creator_on_welcome() {
+    let config = BenchConfig::default();
+    // The creator was never registered, so joiners must refuse the group
+    let key_service = populated_key_service(&config, 2);
+
+    // Key packages pass, so the refusal comes from checking the Welcome
+    for member in key_service.all_data() {
+        check_key_package(&key_service, published(member)).expect("Registered key package");
+    }
+    expect_auth_error(
+        key_service.authenticate(
+            &config.self_credential.credential,
+            config.self_credential.signature_key.as_slice(),
+        ),
+        AuthError::UnknownSignatureKey,
+    );
+    expect_auth_error(
+        create_authenticated_group_with_members(&config, &key_service, &key_service),
+        AuthError::UnknownSignatureKey,
+    );
+}
+
+#[test]
+fn rejects_signature_key_bound_to_other_identity() {
+    let config = BenchConfig::default();
+    let key_service = populated_key_service(&config, 2);
+    let members = key_service.all_data();
+
+    expect_auth_error(
+        key_service.authenticate(
+            &members[0].credential.credential,
+            members[1].credential.signature_key.as_slice(),
+        ),
+        AuthError::IdentityMismatch,
+    );
+}
+
+#[test]
+fn rejects_revoked_signature_key() {
+    let config = BenchConfig::default();
+    let mut key_service = populated_key_service(&config, 2);
+    key_service.revoke(b"Member 1").expect("Known identity");
+
+    let revoked = key_service.member(b"Member 1").expect("Revoked member");
+    expect_auth_error(
+        check_key_package(&key_service, published(revoked)),
+        AuthError::Revoked,
+    );
+    let other = key_service.member(b"Member 2").expect("Other member");
+    check_key_package(&key_service, published(other)).expect("Unrevoked key package");
+}
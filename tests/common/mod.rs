@@ -0,0 +1,21 @@
+// Each test binary only uses some of these
+#![allow(dead_code)]
+
+use openmls_test::key_service::{KeyService, DEFAULT_LIFETIME};
+use openmls_test::mls::BenchConfig;
+
+pub fn populated_key_service(config: &BenchConfig, count: usize) -> KeyService {
+    populated_key_service_with_lifetime(config, count, DEFAULT_LIFETIME)
+}
+
+pub fn populated_key_service_with_lifetime(
+    config: &BenchConfig,
+    count: usize,
+    lifetime: u64,
+) -> KeyService {
+    let mut key_service = KeyService::with_lifetime(lifetime);
+    key_service
+        .generate(&config.ciphersuite, &config.provider, count)
+        .expect("Failed to populate KeyService");
+    key_service
+}
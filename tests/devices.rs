@@ -0,0 +1,27 @@
+use openmls_test::devices::{ratchet_devices, DeviceConfig};
+use openmls_test::key_service::{device_identity, device_user};
+
+#[test]
+fn device_identities_name_their_user() {
+    let identity = device_identity(b"Member 1", 2);
+    assert_eq!(device_user(&identity), b"Member 1");
+    assert_eq!(device_user(b"Alice"), b"Alice");
+}
+
+#[test]
+fn own_devices_receive_their_copy() {
+    let config = DeviceConfig {
+        users: 3,
+        devices_per_user: 3,
+        message_size: 200,
+    };
+    let pairwise = ratchet_devices(&config, false).expect("Failed to measure");
+    let optimized = ratchet_devices(&config, true).expect("Failed to measure");
+
+    // A message per own device, or a payload and a key
+    assert_eq!(pairwise.self_sync_writes, 2);
+    assert_eq!(optimized.self_sync_writes, 4);
+    assert!(pairwise.self_sync_bytes > 2 * 200);
+    assert!(pairwise.remove_device_uploaded > 0);
+    assert_eq!(pairwise.add_device_sessions, config.devices());
+}
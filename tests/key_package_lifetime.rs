@@ -0,0 +1,134 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use openmls_test::key_service::{
+    check_key_package_lifetime, key_package_validity, unix_time, KeyService,
+};
+use openmls_test::mls::{
+    add_member_from_key_service, add_members_checked, create_group, BenchConfig,
+};
+
+use common::populated_key_service_with_lifetime;
+
+const LIFETIME: u64 = 60 * 60;
+
+// Key packages valid until the second they were created in, so they are
+// really expired once that second has passed
+fn expiring_key_service(config: &BenchConfig, count: usize) -> KeyService {
+    let key_service = populated_key_service_with_lifetime(config, count, 0);
+    sleep(Duration::from_millis(2100));
+    key_service
+}
+
+#[test]
+fn validity_comes_from_the_key_package() {
+    let config = BenchConfig::default();
+    let key_service = populated_key_service_with_lifetime(&config, 1, LIFETIME);
+    let key_package = key_service
+        .key_package(b"Member 1", unix_time())
+        .expect("Fresh key package");
+
+    let (not_before, not_after) = key_package_validity(key_package).expect("Lifetime");
+    assert!(not_before <= unix_time());
+    assert!((unix_time() + LIFETIME).abs_diff(not_after) <= 5);
+}
+
+#[test]
+fn refuses_expired_key_package() {
+    let config = BenchConfig::default();
+    let key_service = populated_key_service_with_lifetime(&config, 1, LIFETIME);
+
+    assert!(key_service.key_package(b"Member 1", unix_time()).is_ok());
+    assert!(key_service
+        .key_package(b"Member 1", unix_time() + 2 * LIFETIME)
+        .is_err());
+}
+
+#[test]
+fn rejects_adding_genuinely_expired_key_package() {
+    let config = BenchConfig::default();
+    let key_service = expiring_key_service(&config, 1);
+    let mut group = create_group(&config);
+
+    let member = &key_service.all_data()[0];
+    let key_package = member.key_package.clone().expect("Published key package");
+    assert!(check_key_package_lifetime(&key_package, unix_time()).is_err());
+    assert!(add_members_checked(&mut group, &config, &[key_package], unix_time()).is_err());
+    assert!(add_member_from_key_service(
+        &mut group,
+        &config,
+        &key_service,
+        b"Member 1",
+        unix_time()
+    )
+    .is_err());
+    assert_eq!(group.members().count(), 1);
+}
+
+#[test]
+fn adds_member_with_valid_key_package() {
+    let config = BenchConfig::default();
+    let key_service = populated_key_service_with_lifetime(&config, 1, LIFETIME);
+    let mut group = create_group(&config);
+
+    add_member_from_key_service(&mut group, &config, &key_service, b"Member 1", unix_time())
+        .expect("Valid key package should be accepted");
+    assert_eq!(group.members().count(), 2);
+}
+
+#[test]
+fn collects_only_expired_key_packages() {
+    let config = BenchConfig::default();
+    let mut key_service = expiring_key_service(&config, 3);
+
+    assert_eq!(key_service.collect_garbage(unix_time()), 3);
+    assert!(key_service.key_packages().is_empty());
+    // The members themselves stay registered
+    assert_eq!(key_service.all_data().len(), 3);
+    assert!(key_service.member(b"Member 1").is_some());
+    assert_eq!(key_service.collect_garbage(unix_time()), 0);
+
+    let mut fresh = populated_key_service_with_lifetime(&config, 3, LIFETIME);
+    assert_eq!(fresh.collect_garbage(unix_time()), 0);
+}
+
+#[test]
+fn rotation_refreshes_expiring_key_packages() {
+    let config = BenchConfig::default();
+    let mut key_service = populated_key_service_with_lifetime(&config, 3, LIFETIME);
+    let old_package = key_service
+        .key_package(b"Member 1", unix_time())
+        .expect("Fresh key package")
+        .clone();
+
+    let rotated = key_service
+        .rotate(
+            &config.ciphersuite,
+            &config.provider,
+            unix_time(),
+            2 * LIFETIME,
+        )
+        .expect("Failed to rotate key packages");
+    assert_eq!(rotated, 3);
+
+    let new_package = key_service
+        .key_package(b"Member 1", unix_time())
+        .expect("Rotated key package");
+    assert_ne!(&old_package, new_package);
+}
+
+#[test]
+fn rotation_republishes_collected_key_packages() {
+    let config = BenchConfig::default();
+    let mut key_service = expiring_key_service(&config, 2);
+    key_service.collect_garbage(unix_time());
+
+    // Packages issued from now on still expire immediately, so use no margin
+    let rotated = key_service
+        .rotate(&config.ciphersuite, &config.provider, unix_time(), 0)
+        .expect("Failed to rotate key packages");
+    assert_eq!(rotated, 2);
+    assert_eq!(key_service.key_packages().len(), 2);
+}
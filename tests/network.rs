@@ -0,0 +1,37 @@
+use openmls_test::network::{Latency, Link, Network, NetworkConfig, Partition};
+
+#[test]
+fn rejects_configurations_that_cannot_run() {
+    let certain_loss = NetworkConfig {
+        loss_rate: 1.0,
+        ..Default::default()
+    };
+    assert!(Network::<()>::new(certain_loss).is_err());
+
+    let inverted_range = NetworkConfig {
+        latency: Latency::Uniform(200, 20),
+        ..Default::default()
+    };
+    assert!(Network::<()>::new(inverted_range).is_err());
+}
+
+#[test]
+fn partitions_hold_transmissions_already_in_flight() {
+    let config = NetworkConfig {
+        latency: Latency::Constant(100),
+        partitions: vec![Partition {
+            clients: vec![1],
+            start: 50,
+            end: 500,
+        }],
+        ..Default::default()
+    };
+    let mut network = Network::new(config).expect("Failed to create network");
+    network.send(Link::Down(1), "first");
+    network.send(Link::Down(2), "other");
+
+    assert_eq!(network.next(), Some("other"));
+    assert_eq!(network.now(), 100);
+    assert_eq!(network.next(), Some("first"));
+    assert_eq!(network.now(), 500);
+}
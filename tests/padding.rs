@@ -0,0 +1,83 @@
+use openmls_test::padding::PaddingPolicy;
+
+#[test]
+fn zero_block_size_pads_nothing() {
+    let policy = PaddingPolicy::Block(0);
+    assert_eq!(policy.padded_len(13), 13);
+
+    let padded = policy.pad(b"hello");
+    assert_eq!(padded, b"hello");
+    assert_eq!(policy.unpad(&padded).expect("Failed to unpad"), b"hello");
+}
+
+#[test]
+fn block_padding_roundtrips() {
+    let policy = PaddingPolicy::Block(16);
+    let padded = policy.pad(b"hello");
+    assert_eq!(padded.len(), 16);
+    assert_eq!(policy.unpad(&padded).expect("Failed to unpad"), b"hello");
+}
+
+const POLICIES: [PaddingPolicy; 4] = [
+    PaddingPolicy::None,
+    PaddingPolicy::Block(0),
+    PaddingPolicy::Block(16),
+    PaddingPolicy::PowerOfTwo,
+];
+
+// Payloads that could be mistaken for padding, or land exactly on a block
+fn edge_case_payloads() -> Vec<Vec<u8>> {
+    vec![
+        Vec::new(),
+        vec![0x80],
+        b"ends in marker\x80".to_vec(),
+        b"ends in zero\x00".to_vec(),
+        vec![0; 3],
+        vec![7; 15],
+        vec![7; 16],
+        vec![7; 17],
+    ]
+}
+
+#[test]
+fn every_policy_roundtrips() {
+    for policy in POLICIES {
+        for payload in edge_case_payloads() {
+            let padded = policy.pad(&payload);
+            assert_eq!(padded.len(), policy.padded_len(payload.len()));
+            assert_eq!(
+                policy.unpad(&padded).expect("Failed to unpad"),
+                payload,
+                "{:?} with {:?}",
+                policy,
+                payload
+            );
+        }
+    }
+}
+
+#[test]
+fn exact_block_multiple_gets_a_full_block() {
+    let policy = PaddingPolicy::Block(16);
+    assert_eq!(policy.padded_len(16), 32);
+    assert_eq!(policy.padded_len(15), 16);
+    assert_eq!(policy.padded_len(0), 16);
+}
+
+#[test]
+fn power_of_two_padding_lengths() {
+    let policy = PaddingPolicy::PowerOfTwo;
+    assert_eq!(policy.padded_len(0), 1);
+    assert_eq!(policy.padded_len(3), 4);
+    assert_eq!(policy.padded_len(4), 8);
+    assert_eq!(policy.padded_len(100), 128);
+}
+
+#[test]
+fn padding_without_marker_is_rejected() {
+    for policy in [PaddingPolicy::Block(16), PaddingPolicy::PowerOfTwo] {
+        assert!(policy.unpad(&[0; 16]).is_err());
+        assert!(policy.unpad(&[]).is_err());
+        assert!(policy.unpad(b"no marker\x00\x00").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use openmls::prelude::*;
+use openmls_test::credential::{create_keypackage, make_credential};
+use openmls_test::mls::{create_group, join_from_welcome, receive_message, BenchConfig};
+use openmls_test::psk::{commit_with_psk, resumption_psk_id, store_external_psk};
+
+const PSK_ID: &[u8] = b"shared psk";
+const PSK_SECRET: &[u8] = &[7u8; 32];
+
+// Each participant gets its own provider, so only the PSKs it stored itself
+// are available to it.
+fn key_package(config: &BenchConfig, name: &str) -> KeyPackage {
+    let (credential, signer) = make_credential(&config.ciphersuite, &config.provider, name.into())
+        .expect("Failed to create credential");
+    create_keypackage(config.ciphersuite, &config.provider, credential, &signer)
+        .expect("Failed to create KeyPackage")
+}
+
+fn join(
+    config: &BenchConfig,
+    group: &MlsGroup,
+    welcome: &MlsMessageOut,
+) -> anyhow::Result<MlsGroup> {
+    join_from_welcome(config, welcome, group.export_ratchet_tree().into())
+}
+
+#[test]
+fn external_psk_is_required_to_join() {
+    let alice = BenchConfig::default();
+    let bob = BenchConfig::default();
+    let carol = BenchConfig::default();
+
+    let psk_id = store_external_psk(&alice.provider, alice.ciphersuite, PSK_ID, PSK_SECRET)
+        .expect("Failed to store PSK");
+    store_external_psk(&bob.provider, bob.ciphersuite, PSK_ID, PSK_SECRET)
+        .expect("Failed to store PSK");
+
+    let mut group = create_group(&alice);
+    let (_, welcome) = commit_with_psk(
+        &mut group,
+        &alice.provider,
+        &alice.self_signer,
+        psk_id,
+        &[key_package(&bob, "Bob"), key_package(&carol, "Carol")],
+    )
+    .expect("Failed to commit PSK");
+    let welcome = welcome.expect("Missing Welcome");
+
+    join(&bob, &group, &welcome).expect("Bob holds the PSK");
+    assert!(join(&carol, &group, &welcome).is_err());
+}
+
+#[test]
+fn resumption_psk_reinjection() {
+    let configure = |builder: MlsGroupConfigBuilder| builder.number_of_resumption_psks(4);
+    let alice = BenchConfig::with_group_config(configure);
+    let bob = BenchConfig::with_group_config(configure);
+    let carol = BenchConfig::with_group_config(configure);
+
+    let mut group = create_group(&alice);
+    let (_, welcome, _) = group
+        .add_members(
+            &alice.provider,
+            &alice.self_signer,
+            &[key_package(&bob, "Bob")],
+        )
+        .expect("Failed to add Bob");
+    group
+        .merge_pending_commit(&alice.provider)
+        .expect("Failed to merge add");
+    let mut bob_group = join(&bob, &group, &welcome).expect("Bob failed to join");
+    let resumed_epoch = group.epoch();
+
+    let (update, _, _) = group
+        .self_update(&alice.provider, &alice.self_signer)
+        .expect("Failed to update");
+    group
+        .merge_pending_commit(&alice.provider)
+        .expect("Failed to merge update");
+    receive_message(&mut bob_group, &bob.provider, &update).expect("Bob failed to update");
+
+    let psk_id =
+        resumption_psk_id(&group, &alice.provider, resumed_epoch).expect("Failed to create PSK id");
+    let (commit, welcome) = commit_with_psk(
+        &mut group,
+        &alice.provider,
+        &alice.self_signer,
+        psk_id,
+        &[key_package(&carol, "Carol")],
+    )
+    .expect("Failed to commit resumption PSK");
+
+    receive_message(&mut bob_group, &bob.provider, &commit)
+        .expect("Bob was a member in the resumed epoch");
+    assert_eq!(bob_group.epoch(), group.epoch());
+    assert!(join(&carol, &group, &welcome.expect("Missing Welcome")).is_err());
+}
@@ -0,0 +1,152 @@
+use openmls_test::faults::{simulate_faulty_delivery, FaultConfig};
+use openmls_test::ratchet::{RatchetGroup, MAX_CACHED_KEYS, MAX_SKIPPED_KEYS};
+
+#[test]
+fn reordered_messages_decrypt_from_cache() {
+    let mut group = RatchetGroup::with_generated_members(1);
+    let messages: Vec<_> = (0..5u8)
+        .map(|i| group.encrypt_from_member(0, &[i]))
+        .collect();
+
+    for i in [4, 0, 2, 1, 3] {
+        let (header, ciphertext, nonce) = &messages[i];
+        let plaintext = group
+            .try_decrypt_message(0, header, ciphertext, nonce)
+            .expect("Failed to decrypt message");
+        assert_eq!(plaintext, [i as u8]);
+    }
+    assert_eq!(group.skipped_keys(0), 0);
+}
+
+#[test]
+fn duplicates_are_rejected() {
+    let mut group = RatchetGroup::with_generated_members(1);
+    let first = group.encrypt_from_member(0, b"first");
+    let second = group.encrypt_from_member(0, b"second");
+
+    for (header, ciphertext, nonce) in [&second, &first] {
+        group
+            .try_decrypt_message(0, header, ciphertext, nonce)
+            .expect("Failed to decrypt message");
+    }
+    for (header, ciphertext, nonce) in [&second, &first] {
+        assert!(group
+            .try_decrypt_message(0, header, ciphertext, nonce)
+            .is_err());
+    }
+}
+
+#[test]
+fn lost_messages_stay_in_cache() {
+    let mut group = RatchetGroup::with_generated_members(1);
+    let _lost = group.encrypt_from_member(0, b"lost");
+    let (header, ciphertext, nonce) = group.encrypt_from_member(0, b"delivered");
+
+    group
+        .try_decrypt_message(0, &header, &ciphertext, &nonce)
+        .expect("Failed to decrypt message");
+    assert_eq!(group.skipped_keys(0), 1);
+}
+
+#[test]
+fn failed_decryption_still_advances_the_chain() {
+    let mut group = RatchetGroup::with_generated_members(1);
+    let (lost_header, lost_ciphertext, lost_nonce) = group.encrypt_from_member(0, b"lost");
+    let (header, mut ciphertext, nonce) = group.encrypt_from_member(0, b"tampered");
+    ciphertext[0] ^= 1;
+
+    assert!(group
+        .try_decrypt_message(0, &header, &ciphertext, &nonce)
+        .is_err());
+    // The ratchet skipped the lost message and used up the tampered one's key
+    assert_eq!(group.skipped_keys(0), 1);
+    assert!(group
+        .try_decrypt_message(0, &header, &ciphertext, &nonce)
+        .is_err());
+
+    let plaintext = group
+        .try_decrypt_message(0, &lost_header, &lost_ciphertext, &lost_nonce)
+        .expect("Failed to decrypt message");
+    assert_eq!(plaintext, b"lost");
+    assert_eq!(group.skipped_keys(0), 0);
+}
+
+#[test]
+fn many_small_gaps_add_up_past_the_limit() {
+    let mut group = RatchetGroup::with_generated_members(1);
+    // Every third message is lost, more than the limit in total
+    for i in 0..3 * MAX_SKIPPED_KEYS + 10 {
+        let (header, ciphertext, nonce) = group.encrypt_from_member(0, b"message");
+        if i % 3 == 0 {
+            continue;
+        }
+        group
+            .try_decrypt_message(0, &header, &ciphertext, &nonce)
+            .expect("Failed to decrypt message");
+    }
+    assert!(group.skipped_keys(0) > MAX_SKIPPED_KEYS);
+}
+
+#[test]
+fn oldest_keys_are_evicted() {
+    let mut group = RatchetGroup::with_generated_members(1);
+    let mut lost = Vec::new();
+    for i in 0..2 * MAX_CACHED_KEYS + 10 {
+        let message = group.encrypt_from_member(0, b"message");
+        if i % 2 == 0 {
+            lost.push(message);
+            continue;
+        }
+        let (header, ciphertext, nonce) = &message;
+        group
+            .try_decrypt_message(0, header, ciphertext, nonce)
+            .expect("Failed to decrypt message");
+    }
+    assert_eq!(group.skipped_keys(0), MAX_CACHED_KEYS);
+
+    let (header, ciphertext, nonce) = &lost[0];
+    assert!(group
+        .try_decrypt_message(0, header, ciphertext, nonce)
+        .is_err());
+    let (header, ciphertext, nonce) = lost.last().expect("Messages were lost");
+    group
+        .try_decrypt_message(0, header, ciphertext, nonce)
+        .expect("Failed to decrypt message");
+}
+
+#[test]
+fn gaps_beyond_the_limit_are_rejected() {
+    let mut group = RatchetGroup::with_generated_members(1);
+    let messages: Vec<_> = (0..=MAX_SKIPPED_KEYS + 1)
+        .map(|_| group.encrypt_from_member(0, b"message"))
+        .collect();
+
+    let (header, ciphertext, nonce) = &messages[MAX_SKIPPED_KEYS + 1];
+    assert!(group
+        .try_decrypt_message(0, header, ciphertext, nonce)
+        .is_err());
+    assert_eq!(group.skipped_keys(0), 0);
+
+    // Exactly at the limit is still fine
+    let (header, ciphertext, nonce) = &messages[MAX_SKIPPED_KEYS];
+    group
+        .try_decrypt_message(0, header, ciphertext, nonce)
+        .expect("Failed to decrypt message");
+    assert_eq!(group.skipped_keys(0), MAX_SKIPPED_KEYS);
+}
+
+#[test]
+fn faulty_delivery_accounts_for_every_message() {
+    let faults = FaultConfig {
+        reorder_window: 20,
+        drop_rate: 0.05,
+        duplicate_rate: 0.1,
+        seed: 3,
+    };
+    let report = simulate_faulty_delivery(3, 200, &faults);
+
+    assert_eq!(report.sent, 600);
+    assert_eq!(report.decrypted + report.dropped, report.sent);
+    assert_eq!(report.rejected_by_limit, 0);
+    assert!(report.max_skipped_keys <= MAX_SKIPPED_KEYS);
+}
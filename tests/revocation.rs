@@ -0,0 +1,218 @@
+mod common;
+
+use openmls::prelude::*;
+use openmls_test::key_service::KeyService;
+use openmls_test::mls::{
+    create_bare_group_with_member_states, own_signature_key, receive_message, BenchConfig,
+    MemberState, Received,
+};
+use openmls_test::ratchet::RatchetGroup;
+use openmls_test::revocation::{
+    is_designated_committer, receive_enforcing_revocation, receive_ratchet_enforcing_revocation,
+    remove_revoked_members,
+};
+
+use common::populated_key_service;
+
+fn revoke(key_service: &mut KeyService, member: &MemberState) {
+    let identity = key_service
+        .member_by_signature_key(&own_signature_key(&member.group))
+        .expect("Unknown member")
+        .credential
+        .credential
+        .identity()
+        .to_vec();
+    key_service.revoke(&identity).expect("Failed to revoke");
+}
+
+#[test]
+fn designated_committer_removes_revoked_members() {
+    let config = BenchConfig::default();
+    let mut key_service = populated_key_service(&config, 3);
+    key_service.trust(&config.self_credential);
+    let (mut group, mut members) = create_bare_group_with_member_states(&config, &key_service);
+    revoke(&mut key_service, &members[0]);
+    let revoked_leaf = members[0].group.own_leaf_index();
+
+    // Only the creator, at the lowest leaf, commits
+    let other = &mut members[1];
+    let commit = remove_revoked_members(
+        &mut other.group,
+        &config.provider,
+        &other.signer,
+        &key_service,
+    )
+    .expect("Failed to check members");
+    assert!(commit.is_none());
+
+    let commit = remove_revoked_members(
+        &mut group,
+        &config.provider,
+        &config.self_signer,
+        &key_service,
+    )
+    .expect("Failed to remove members");
+    assert!(commit.is_some());
+    assert_eq!(group.members().count(), 3);
+    assert!(group.members().all(|member| member.index != revoked_leaf));
+
+    // Nothing is left to remove
+    let commit = remove_revoked_members(
+        &mut group,
+        &config.provider,
+        &config.self_signer,
+        &key_service,
+    )
+    .expect("Failed to check members");
+    assert!(commit.is_none());
+}
+
+#[test]
+fn designated_committer_is_lowest_member_in_good_standing() {
+    let config = BenchConfig::default();
+    // The creator is never registered, so it is not in good standing
+    let key_service = populated_key_service(&config, 3);
+    let (group, members) = create_bare_group_with_member_states(&config, &key_service);
+
+    assert!(!is_designated_committer(&group, &key_service));
+    let lowest = members
+        .iter()
+        .map(|member| member.group.own_leaf_index())
+        .min()
+        .expect("Group has members");
+    for member in &members {
+        assert_eq!(
+            is_designated_committer(&member.group, &key_service),
+            member.group.own_leaf_index() == lowest
+        );
+    }
+}
+
+#[test]
+fn accepts_messages_from_members_in_good_standing() {
+    let config = BenchConfig::default();
+    let mut key_service = populated_key_service(&config, 2);
+    key_service.trust(&config.self_credential);
+    let (mut group, mut members) = create_bare_group_with_member_states(&config, &key_service);
+
+    let sender = &mut members[0];
+    let message = sender
+        .group
+        .create_message(&config.provider, &sender.signer, b"hello")
+        .expect("Failed to create message");
+    let (received, removal) = receive_enforcing_revocation(
+        &mut group,
+        &config.provider,
+        &config.self_signer,
+        &message,
+        &key_service,
+    )
+    .expect("Failed to process message");
+
+    assert!(matches!(received, Some(Received::Application(bytes)) if bytes == b"hello"));
+    assert!(removal.is_none());
+}
+
+#[test]
+fn discards_messages_from_revoked_members_and_removes_them() {
+    let config = BenchConfig::default();
+    let mut key_service = populated_key_service(&config, 3);
+    key_service.trust(&config.self_credential);
+    let (mut group, mut members) = create_bare_group_with_member_states(&config, &key_service);
+    revoke(&mut key_service, &members[0]);
+
+    let revoked = members.remove(0);
+    let message = revoked
+        .group
+        .create_message(&config.provider, &revoked.signer, b"hello")
+        .expect("Failed to create message");
+
+    let (received, removal) = receive_enforcing_revocation(
+        &mut group,
+        &config.provider,
+        &config.self_signer,
+        &message,
+        &key_service,
+    )
+    .expect("Failed to process message");
+    assert!(received.is_none());
+    let removal = removal.expect("Revocation was not detected");
+
+    for member in members.iter_mut() {
+        let (received, own_removal) = receive_enforcing_revocation(
+            &mut member.group,
+            &config.provider,
+            &member.signer,
+            &message,
+            &key_service,
+        )
+        .expect("Failed to process message");
+        assert!(received.is_none());
+        assert!(own_removal.is_none());
+
+        receive_message(&mut member.group, &config.provider, &removal)
+            .expect("Failed to process removal");
+        assert_eq!(member.group.members().count(), 3);
+        assert_eq!(member.group.epoch(), group.epoch());
+    }
+}
+
+#[test]
+fn ratchet_drops_revoked_session_and_rekeys() {
+    let config = BenchConfig::default();
+    let mut key_service = populated_key_service(&config, 3);
+    let mut peers: Vec<CredentialWithKey> = key_service
+        .all_data()
+        .into_iter()
+        .map(|member| member.credential.clone())
+        .collect();
+    let mut ratchet_group = RatchetGroup::with_generated_members(peers.len());
+
+    let message = ratchet_group.encrypt_from_member(1, b"hello");
+    let (received, rekey) = receive_ratchet_enforcing_revocation(
+        &mut ratchet_group,
+        &mut peers,
+        1,
+        &message,
+        &key_service,
+    )
+    .expect("Failed to process message");
+    assert_eq!(received.as_deref(), Some(&b"hello"[..]));
+    assert!(rekey.is_none());
+
+    let revoked = peers[0].clone();
+    let identity = key_service
+        .member_by_signature_key(revoked.signature_key.as_slice())
+        .expect("Unknown member")
+        .credential
+        .credential
+        .identity()
+        .to_vec();
+    key_service.revoke(&identity).expect("Failed to revoke");
+
+    let message = ratchet_group.encrypt_from_member(0, b"hello");
+    let (received, rekey) = receive_ratchet_enforcing_revocation(
+        &mut ratchet_group,
+        &mut peers,
+        0,
+        &message,
+        &key_service,
+    )
+    .expect("Failed to process message");
+    assert!(received.is_none());
+    assert_eq!(rekey.expect("Revocation was not detected").len(), 2);
+    assert_eq!(ratchet_group.len(), 2);
+    assert!(!peers.contains(&revoked));
+
+    // The remaining sessions still work after the rekey
+    let message = ratchet_group.encrypt_from_member(0, b"after");
+    let (received, _) = receive_ratchet_enforcing_revocation(
+        &mut ratchet_group,
+        &mut peers,
+        0,
+        &message,
+        &key_service,
+    )
+    .expect("Failed to process message");
+    assert_eq!(received.as_deref(), Some(&b"after"[..]));
+}
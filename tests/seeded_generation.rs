@@ -0,0 +1,82 @@
+use openmls::prelude::*;
+use openmls_test::key_service::{KeyService, MemberData};
+use openmls_test::mls::BenchConfig;
+
+const COUNT: usize = 16;
+const SEED: u64 = 42;
+
+fn identities() -> Vec<Vec<u8>> {
+    (1..=COUNT)
+        .map(|i| format!("Member {}", i).into_bytes())
+        .collect()
+}
+
+fn init_key(member: &MemberData) -> Vec<u8> {
+    member
+        .key_package
+        .as_ref()
+        .expect("Key package was collected")
+        .hpke_init_key()
+        .tls_serialize_detached()
+        .expect("Failed to encode init key")
+}
+
+#[test]
+fn parallel_and_sequential_generation_agree() {
+    let config = BenchConfig::default();
+    let mut sequential = KeyService::new();
+    sequential
+        .generate_seeded(&config.ciphersuite, &config.provider, COUNT, SEED)
+        .expect("Failed to generate members");
+    let mut parallel = KeyService::new();
+    parallel
+        .generate_parallel(
+            &config.ciphersuite,
+            &config.provider,
+            COUNT,
+            SEED,
+            |_, _| {},
+        )
+        .expect("Failed to generate members");
+
+    for identity in identities() {
+        let a = sequential.member(&identity).expect("Sequential member");
+        let b = parallel.member(&identity).expect("Parallel member");
+        assert_eq!(a.credential, b.credential);
+        assert_eq!(a.signature_pair.public(), b.signature_pair.public());
+        // The package lifetime is taken from the clock, so only keys are
+        // compared
+        assert_eq!(init_key(a), init_key(b));
+    }
+}
+
+#[test]
+fn different_seeds_give_different_keys() {
+    let config = BenchConfig::default();
+    let mut first = KeyService::new();
+    first
+        .generate_seeded(&config.ciphersuite, &config.provider, COUNT, SEED)
+        .expect("Failed to generate members");
+    let mut second = KeyService::new();
+    second
+        .generate_seeded(&config.ciphersuite, &config.provider, COUNT, SEED + 1)
+        .expect("Failed to generate members");
+
+    for identity in identities() {
+        let a = first.member(&identity).expect("First member");
+        let b = second.member(&identity).expect("Second member");
+        assert_ne!(a.signature_pair.public(), b.signature_pair.public());
+        assert_ne!(init_key(a), init_key(b));
+    }
+}
+
+#[test]
+fn seeded_generation_requires_ed25519() {
+    let config = BenchConfig::default();
+    let ciphersuite = Ciphersuite::MLS_128_DHKEMP256_AES128GCM_SHA256_P256;
+    let mut key_service = KeyService::new();
+
+    assert!(key_service
+        .generate_seeded(&ciphersuite, &config.provider, 1, SEED)
+        .is_err());
+}
@@ -0,0 +1,96 @@
+use openmls_test::mls::BenchConfig;
+use openmls_test::synthetic::{generate, GroupSizeDistribution, SizeDistribution, WorkloadConfig};
+use openmls_test::workload::{replay, Action, Event, MlsProtocol, RatchetProtocol, Workload};
+
+fn workload(actions: Vec<Action>) -> Workload {
+    Workload {
+        events: actions
+            .into_iter()
+            .enumerate()
+            .map(|(time, action)| Event {
+                time: time as u64,
+                action,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn rejects_sends_from_non_members() {
+    let mut protocol = RatchetProtocol::new(false);
+    let workload = workload(vec![
+        Action::Join { member: 1 },
+        Action::Send {
+            member: 2,
+            size: 100,
+        },
+    ]);
+    assert!(replay(&mut protocol, &workload).is_err());
+}
+
+#[test]
+fn creator_receives_messages() {
+    let mut protocol = RatchetProtocol::new(false);
+    let workload = workload(vec![
+        Action::Join { member: 1 },
+        Action::Send {
+            member: 1,
+            size: 100,
+        },
+    ]);
+    let report = replay(&mut protocol, &workload).expect("Failed to replay");
+
+    // The join instruction and the message both reach the creator
+    assert_eq!(report.queue_writes, 2);
+    assert!(report.downloaded_bytes > 100);
+}
+
+#[test]
+fn creator_does_not_receive_its_own_commits() {
+    let mut protocol = MlsProtocol::new(BenchConfig::default());
+    let workload = workload(vec![
+        Action::Join { member: 1 },
+        Action::Join { member: 2 },
+        Action::Leave { member: 1 },
+    ]);
+    let report = replay(&mut protocol, &workload).expect("Failed to replay");
+
+    // A Welcome per joiner, the second add reaches member 1 and the removal
+    // reaches member 2
+    assert_eq!(report.queue_writes, 4);
+}
+
+#[test]
+fn rejects_inverted_uniform_ranges() {
+    let config = WorkloadConfig {
+        group_size: GroupSizeDistribution::Uniform(10, 3),
+        ..WorkloadConfig::small_chatty()
+    };
+    assert!(generate(&config, 0).is_err());
+
+    let config = WorkloadConfig {
+        message_size: SizeDistribution::Uniform(100, 10),
+        ..WorkloadConfig::small_chatty()
+    };
+    assert!(generate(&config, 0).is_err());
+}
+
+#[test]
+fn optimized_sends_write_twice_per_recipient() {
+    let workload = workload(vec![
+        Action::Join { member: 1 },
+        Action::Join { member: 2 },
+        Action::Join { member: 3 },
+        Action::Send {
+            member: 1,
+            size: 1000,
+        },
+    ]);
+    let pairwise = replay(&mut RatchetProtocol::new(false), &workload).expect("Failed to replay");
+    let optimized = replay(&mut RatchetProtocol::new(true), &workload).expect("Failed to replay");
+
+    // Members 2 and 3 and the creator each get a payload and a key
+    assert_eq!(optimized.queue_writes - pairwise.queue_writes, 3);
+    // The payload is stored once instead of once per recipient
+    assert!(optimized.stored_bytes < pairwise.stored_bytes);
+}
@@ -0,0 +1,35 @@
+use openmls_test::credential::make_x509_credential;
+use openmls_test::mls::BenchConfig;
+use openmls_test::x509::{credential_chain, decode_chain, encode_chain, TestCa};
+
+#[test]
+fn chain_roundtrips() {
+    let chain = vec![vec![1u8; 300], vec![], vec![2u8; 70000]];
+    let encoded = encode_chain(&chain).expect("Failed to encode chain");
+
+    assert_eq!(
+        decode_chain(&encoded).expect("Failed to decode chain"),
+        chain
+    );
+}
+
+#[test]
+fn rejects_truncated_chain() {
+    let encoded = encode_chain(&[vec![1u8; 300]]).expect("Failed to encode chain");
+
+    assert!(decode_chain(&encoded[..encoded.len() - 1]).is_err());
+}
+
+#[test]
+fn credential_carries_leaf_and_ca_certificates() {
+    let config = BenchConfig::default();
+    let ca = TestCa::new("Test CA").expect("Failed to create CA");
+    let (credential, _) =
+        make_x509_credential(&config.ciphersuite, &config.provider, &ca, "Bob".into())
+            .expect("Failed to create credential");
+
+    let chain = credential_chain(&credential.credential).expect("Failed to decode chain");
+    assert_eq!(chain.len(), 2);
+    assert_eq!(chain[1], ca.certificate_der());
+    assert_ne!(chain[0], chain[1]);
+}